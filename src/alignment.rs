@@ -0,0 +1,94 @@
+pub mod alignment {
+    // Similarity of whole sequences, used to rank the sequences matched by match_sequence.
+    // Every function is a dynamic programming alignment keeping only two rows of the table.
+    use crate::cpt::cpt::SequenceMatchFunction;
+    use crate::data_types::data_types::{DataTypes, SimilarityScores};
+    use crate::errors::errors::CptError;
+
+    // Costs of the edit operations of the Levenshtein distance.
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    pub struct EditCosts {
+        pub insertion: usize,
+        pub deletion: usize,
+        pub substitution: usize,
+    }
+
+    impl Default for EditCosts {
+        fn default() -> Self {
+            EditCosts { insertion: 1, deletion: 1, substitution: 1 }
+        }
+    }
+
+    // Minimal cost of the edits turning `query` into `other`.
+    pub fn levenshtein(query: &[DataTypes], other: &[DataTypes], costs: &EditCosts) -> usize {
+        // previous[j] is the cost of turning the first i - 1 items of query into the first j items of other
+        let mut previous: Vec<usize> = (0..=other.len()).map(|j| j * costs.insertion).collect();
+        let mut current = vec![0; other.len() + 1];
+        for (i, query_item) in query.iter().enumerate() {
+            current[0] = (i + 1) * costs.deletion;
+            for (j, other_item) in other.iter().enumerate() {
+                let substitution = if query_item == other_item { 0 } else { costs.substitution };
+                current[j + 1] = (previous[j] + substitution)
+                    .min(previous[j + 1] + costs.deletion)
+                    .min(current[j] + costs.insertion);
+            }
+            std::mem::swap(&mut previous, &mut current);
+        }
+        previous[other.len()]
+    }
+
+    // Length of the longest subsequence common to both sequences, items do not need to be contiguous.
+    pub fn longest_common_subsequence(query: &[DataTypes], other: &[DataTypes]) -> usize {
+        let mut previous = vec![0; other.len() + 1];
+        let mut current = vec![0; other.len() + 1];
+        for query_item in query.iter() {
+            for (j, other_item) in other.iter().enumerate() {
+                current[j + 1] = if query_item == other_item {
+                    previous[j] + 1
+                } else {
+                    previous[j + 1].max(current[j])
+                };
+            }
+            std::mem::swap(&mut previous, &mut current);
+        }
+        previous[other.len()]
+    }
+
+    // Dynamic time warping distance between two numeric sequences: the sum of the item
+    // distances along the best alignment, where an item can be matched with several items
+    // of the other sequence. Sequences that are shifted in time have a small distance.
+    pub fn dynamic_time_warping(query: &[DataTypes], other: &[DataTypes]) -> Result<f32, CptError> {
+        if query.is_empty() || other.is_empty() {
+            return Ok(if query.len() == other.len() { 0.0 } else { f32::INFINITY });
+        }
+        let mut previous = vec![f32::INFINITY; other.len() + 1];
+        let mut current = vec![f32::INFINITY; other.len() + 1];
+        previous[0] = 0.0;
+        for &query_item in query.iter() {
+            current[0] = f32::INFINITY;
+            for (j, &other_item) in other.iter().enumerate() {
+                current[j + 1] = item_distance(query_item, other_item)? + previous[j].min(previous[j + 1]).min(current[j]);
+            }
+            std::mem::swap(&mut previous, &mut current);
+        }
+        Ok(previous[other.len()])
+    }
+
+    fn item_distance(a: DataTypes, b: DataTypes) -> Result<f32, CptError> {
+        match (a, b) {
+            (DataTypes::Integer(a), DataTypes::Integer(b)) => Ok((a as f32 - b as f32).abs()),
+            (DataTypes::U8(a), DataTypes::U8(b)) => Ok((a as f32 - b as f32).abs()),
+            _ => Err(CptError::IncompatibleTypes(format!("Cannot compute the distance between {:?} and {:?}", a, b)))
+        }
+    }
+
+    // Scores `other` against `query` with a sequence metric of `SequenceMatchFunction`.
+    pub fn sequence_similarity(match_function: SequenceMatchFunction, query: &[DataTypes], other: &[DataTypes]) -> Result<SimilarityScores, CptError> {
+        match match_function {
+            SequenceMatchFunction::EditDistance(costs) => Ok(SimilarityScores::Distance(levenshtein(query, other, &costs) as f32)),
+            SequenceMatchFunction::LongestCommonSubsequence => Ok(SimilarityScores::Length(longest_common_subsequence(query, other))),
+            SequenceMatchFunction::DynamicTimeWarping => Ok(SimilarityScores::Distance(dynamic_time_warping(query, other)?)),
+            _ => Err(CptError::IncompatibleTypes(format!("{:?} compares items, not sequences", match_function)))
+        }
+    }
+}
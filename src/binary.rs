@@ -0,0 +1,347 @@
+pub mod binary {
+    // Compact binary format of a CPT<DataTypes>, all integers are LEB128 varints unless stated otherwise:
+    //
+    //  magic           b"CPTB"
+    //  version         u16, little endian
+    //  node count
+    //  nodes           data, parent, children (see write_node)
+    //  inverted index  value count, then for each value: data, node id count, delta encoded node ids
+    //  lookup table    entry count, then for each sequence: delta encoded last node id, attributes
+    //  checksum        CRC-32 of all the previous bytes, u32, little endian
+    //
+    // Node ids are delta encoded against a nearby id, and the deltas are zigzag encoded
+    // so that ids that are not sorted are still supported.
+    use std::collections::HashMap;
+    use std::convert::TryFrom;
+    use std::io::{Read, Write, Error, ErrorKind};
+
+    use crate::cpt::cpt::{CPT, InvertedIndex};
+    use crate::data_types::data_types::{DataTypes, SequenceAttributes};
+    use crate::nodes::nodes::{Node, NodeId};
+
+    pub const MAGIC: [u8; 4] = *b"CPTB";
+    pub const FORMAT_VERSION: u16 = 1;
+
+    // Collections are never pre-allocated beyond this size, so that a corrupt
+    // count cannot make the reader allocate gigabytes before failing
+    const MAX_PREALLOCATION: usize = 1 << 16;
+
+    const fn crc32_table() -> [u32; 256] {
+        let mut table = [0u32; 256];
+        let mut index = 0;
+        while index < 256 {
+            let mut crc = index as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+                bit += 1;
+            }
+            table[index] = crc;
+            index += 1;
+        }
+        table
+    }
+
+    const CRC32_TABLE: [u32; 256] = crc32_table();
+
+    fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+        !bytes.iter().fold(!crc, |crc, &byte| {
+            CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+        })
+    }
+
+    fn corrupt(message: String) -> Error {
+        Error::new(ErrorKind::InvalidData, message)
+    }
+
+    fn zigzag(value: i64) -> u64 {
+        ((value << 1) ^ (value >> 63)) as u64
+    }
+
+    fn unzigzag(value: u64) -> i64 {
+        ((value >> 1) as i64) ^ -((value & 1) as i64)
+    }
+
+    struct BinaryWriter<W: Write> {
+        inner: W,
+        crc: u32
+    }
+
+    impl<W: Write> BinaryWriter<W> {
+        fn write_bytes(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+            self.crc = crc32_update(self.crc, bytes);
+            self.inner.write_all(bytes)
+        }
+
+        fn write_varint(&mut self, mut value: u64) -> std::io::Result<()> {
+            let mut buffer = [0u8; 10];
+            let mut length = 0;
+            loop {
+                let byte = (value & 0x7F) as u8;
+                value >>= 7;
+                if value == 0 {
+                    buffer[length] = byte;
+                    length += 1;
+                    break;
+                }
+                buffer[length] = byte | 0x80;
+                length += 1;
+            }
+            self.write_bytes(&buffer[..length])
+        }
+
+        fn write_delta(&mut self, value: NodeId, reference: NodeId) -> std::io::Result<()> {
+            self.write_varint(zigzag(value as i64 - reference as i64))
+        }
+
+        fn write_data(&mut self, data: Option<DataTypes>) -> std::io::Result<()> {
+            // A tag byte, followed by the value
+            match data {
+                None => self.write_bytes(&[0]),
+                Some(DataTypes::Integer(value)) => {
+                    self.write_bytes(&[1])?;
+                    self.write_varint(value as u64)
+                },
+                Some(DataTypes::U8(value)) => self.write_bytes(&[2, value])
+            }
+        }
+
+        fn write_node(&mut self, id: NodeId, node: &Node<DataTypes>) -> std::io::Result<()> {
+            // The parent is stored as 0 for the root, and as its delta to the node id plus one otherwise.
+            // Each child is stored as its delta to the previous child, the first one to the node id.
+            self.write_data(node.data)?;
+            match node.parent {
+                None => self.write_varint(0)?,
+                Some(parent) => self.write_varint(zigzag(id as i64 - parent as i64) + 1)?
+            }
+            self.write_varint(node.children.len() as u64)?;
+            let mut previous = id;
+            for &child in node.children.iter() {
+                self.write_delta(child, previous)?;
+                previous = child;
+            }
+            Ok(())
+        }
+    }
+
+    struct BinaryReader<R: Read> {
+        inner: R,
+        crc: u32
+    }
+
+    impl<R: Read> BinaryReader<R> {
+        fn read_bytes(&mut self, bytes: &mut [u8]) -> std::io::Result<()> {
+            self.inner.read_exact(bytes)?;
+            self.crc = crc32_update(self.crc, bytes);
+            Ok(())
+        }
+
+        fn read_byte(&mut self) -> std::io::Result<u8> {
+            let mut byte = [0u8; 1];
+            self.read_bytes(&mut byte)?;
+            Ok(byte[0])
+        }
+
+        fn read_varint(&mut self) -> std::io::Result<u64> {
+            let mut value: u64 = 0;
+            let mut shift = 0;
+            loop {
+                let byte = self.read_byte()?;
+                if shift >= 64 || (shift == 63 && byte > 1) {
+                    return Err(corrupt("Varint overflows 64 bits".to_string()));
+                }
+                value |= ((byte & 0x7F) as u64) << shift;
+                if byte & 0x80 == 0 {
+                    return Ok(value);
+                }
+                shift += 7;
+            }
+        }
+
+        fn read_length(&mut self) -> std::io::Result<usize> {
+            let length = self.read_varint()?;
+            usize::try_from(length).map_err(|_| corrupt(format!("Length {} does not fit in memory", length)))
+        }
+
+        fn read_delta(&mut self, reference: NodeId) -> std::io::Result<NodeId> {
+            let delta = unzigzag(self.read_varint()?);
+            i64::try_from(reference).ok()
+                .and_then(|reference| reference.checked_add(delta))
+                .and_then(|value| NodeId::try_from(value).ok())
+                .ok_or_else(|| corrupt(format!("Node id delta {} from {} is out of range", delta, reference)))
+        }
+
+        fn read_data(&mut self) -> std::io::Result<Option<DataTypes>> {
+            match self.read_byte()? {
+                0 => Ok(None),
+                1 => Ok(Some(DataTypes::Integer(self.read_length()?))),
+                2 => Ok(Some(DataTypes::U8(self.read_byte()?))),
+                tag => Err(corrupt(format!("Unknown data tag {}", tag)))
+            }
+        }
+
+        fn read_node(&mut self, id: NodeId) -> std::io::Result<Node<DataTypes>> {
+            let data = self.read_data()?;
+            let parent = match self.read_varint()? {
+                0 => None,
+                delta => {
+                    // 0 means no parent, so the deltas are shifted by one
+                    let parent = delta.checked_sub(1)
+                        .and_then(|delta| i64::try_from(id).ok()?.checked_sub(unzigzag(delta)))
+                        .and_then(|parent| NodeId::try_from(parent).ok())
+                        .ok_or_else(|| corrupt(format!("Parent id of node {} is out of range", id)))?;
+                    Some(parent)
+                }
+            };
+            let child_count = self.read_length()?;
+            let mut children = Vec::<NodeId>::with_capacity(child_count.min(MAX_PREALLOCATION));
+            let mut previous = id;
+            for _ in 0..child_count {
+                previous = self.read_delta(previous)?;
+                children.push(previous);
+            }
+            Ok(Node { parent, children, data })
+        }
+
+        fn read_attribute(&mut self) -> std::io::Result<SequenceAttributes> {
+            match self.read_byte()? {
+                0 => {
+                    let length = self.read_length()?;
+                    // Read through `take` so that a corrupt length cannot allocate more than the stream holds
+                    let mut bytes = Vec::<u8>::with_capacity(length.min(MAX_PREALLOCATION));
+                    (&mut self.inner).take(length as u64).read_to_end(&mut bytes)?;
+                    if bytes.len() != length {
+                        return Err(Error::new(ErrorKind::UnexpectedEof, "Truncated attribute"));
+                    }
+                    self.crc = crc32_update(self.crc, &bytes);
+                    String::from_utf8(bytes)
+                        .map(SequenceAttributes::ClassStr)
+                        .map_err(|e| corrupt(format!("Invalid attribute string: {}", e)))
+                },
+                tag => Err(corrupt(format!("Unknown attribute tag {}", tag)))
+            }
+        }
+    }
+
+    impl CPT<DataTypes> {
+        // Writes the CPT in the compact binary format.
+        pub fn write_binary<W: Write>(&self, writer: W) -> std::io::Result<()> {
+            let mut writer = BinaryWriter { inner: writer, crc: 0 };
+            writer.write_bytes(&MAGIC)?;
+            writer.write_bytes(&FORMAT_VERSION.to_le_bytes())?;
+
+            writer.write_varint(self.nodes.len() as u64)?;
+            for (id, node) in self.nodes.iter().enumerate() {
+                writer.write_node(id, node)?;
+            }
+
+            writer.write_varint(self.inverted_index.values.len() as u64)?;
+            for (value, node_ids) in self.inverted_index.values.iter().zip(self.inverted_index.node_ids.iter()) {
+                writer.write_data(Some(*value))?;
+                writer.write_varint(node_ids.len() as u64)?;
+                let mut previous = 0;
+                for &node_id in node_ids.iter() {
+                    writer.write_delta(node_id, previous)?;
+                    previous = node_id;
+                }
+            }
+
+            // Sort the lookup table so that the same model always gives the same bytes
+            let mut sequences: Vec<(&NodeId, &Vec<SequenceAttributes>)> = self.sequences_lookup_table.iter().collect();
+            sequences.sort_by_key(|sequence| *sequence.0);
+            writer.write_varint(sequences.len() as u64)?;
+            let mut previous = 0;
+            for (&last_node_id, attributes) in sequences {
+                writer.write_delta(last_node_id, previous)?;
+                previous = last_node_id;
+                writer.write_varint(attributes.len() as u64)?;
+                for attribute in attributes.iter() {
+                    match attribute {
+                        SequenceAttributes::ClassStr(class) => {
+                            writer.write_bytes(&[0])?;
+                            writer.write_varint(class.len() as u64)?;
+                            writer.write_bytes(class.as_bytes())?;
+                        }
+                    }
+                }
+            }
+
+            let checksum = writer.crc;
+            writer.inner.write_all(&checksum.to_le_bytes())?;
+            writer.inner.flush()
+        }
+
+        // Returns the CPT in the compact binary format.
+        pub fn to_binary(&self) -> Vec<u8> {
+            let mut bytes = Vec::<u8>::new();
+            self.write_binary(&mut bytes).expect("Writing to a Vec cannot fail");
+            bytes
+        }
+
+        // Reads a CPT written by `write_binary`.
+        // Corrupt or unsupported data is reported as an `InvalidData` error.
+        pub fn read_binary<R: Read>(reader: R) -> std::io::Result<CPT<DataTypes>> {
+            let mut reader = BinaryReader { inner: reader, crc: 0 };
+            let mut magic = [0u8; 4];
+            reader.read_bytes(&mut magic)?;
+            if magic != MAGIC {
+                return Err(corrupt("Not a binary CPT model".to_string()));
+            }
+            let mut version = [0u8; 2];
+            reader.read_bytes(&mut version)?;
+            let version = u16::from_le_bytes(version);
+            if version != FORMAT_VERSION {
+                return Err(corrupt(format!("Unsupported format version {}", version)));
+            }
+
+            let node_count = reader.read_length()?;
+            let mut nodes = Vec::<Node<DataTypes>>::with_capacity(node_count.min(MAX_PREALLOCATION));
+            for id in 0..node_count {
+                nodes.push(reader.read_node(id)?);
+            }
+
+            let mut inverted_index = InvertedIndex::<DataTypes>::new();
+            let value_count = reader.read_length()?;
+            for _ in 0..value_count {
+                let value = reader.read_data()?.ok_or_else(|| corrupt("Missing value in inverted index".to_string()))?;
+                let node_id_count = reader.read_length()?;
+                let mut node_ids = Vec::<NodeId>::with_capacity(node_id_count.min(MAX_PREALLOCATION));
+                let mut previous = 0;
+                for _ in 0..node_id_count {
+                    previous = reader.read_delta(previous)?;
+                    node_ids.push(previous);
+                }
+                inverted_index.values.push(value);
+                inverted_index.node_ids.push(node_ids);
+            }
+
+            let sequence_count = reader.read_length()?;
+            let mut sequences_lookup_table = HashMap::<NodeId, Vec<SequenceAttributes>>::with_capacity(sequence_count.min(MAX_PREALLOCATION));
+            let mut previous = 0;
+            for _ in 0..sequence_count {
+                previous = reader.read_delta(previous)?;
+                let attribute_count = reader.read_length()?;
+                let mut attributes = Vec::<SequenceAttributes>::with_capacity(attribute_count.min(MAX_PREALLOCATION));
+                for _ in 0..attribute_count {
+                    attributes.push(reader.read_attribute()?);
+                }
+                sequences_lookup_table.insert(previous, attributes);
+            }
+
+            let expected_checksum = reader.crc;
+            let mut checksum = [0u8; 4];
+            reader.inner.read_exact(&mut checksum)?;
+            if u32::from_le_bytes(checksum) != expected_checksum {
+                return Err(corrupt("Checksum mismatch".to_string()));
+            }
+
+            let mut cpt = CPT::new();
+            cpt.nodes = nodes;
+            cpt.inverted_index = inverted_index;
+            cpt.sequences_lookup_table = sequences_lookup_table;
+            // The number of items inserted is not saved
+            cpt.items_inserted = None;
+            Ok(cpt)
+        }
+    }
+}
@@ -0,0 +1,385 @@
+pub mod compact {
+    // Memory-lean layout of a CPT<DataTypes>, for models with many nodes, like byte-level models.
+    //
+    // Node ids are 32 bits, and every per node field is stored in its own array, so that
+    // no padding is added between fields. The root has neither data nor parent, it is kept
+    // out of band: only its children are stored, and node i (i >= 1) is at position i - 1.
+    // Children are sorted by data so that training can find a child with a binary search.
+    // Small child lists are stored inline, larger ones are moved to the heap.
+    use std::collections::HashMap;
+    use std::convert::TryFrom;
+    use std::mem::size_of;
+    use serde::Serialize;
+
+    use crate::cpt::cpt::{CPT, InvertedIndex, MatchOptions, PredictedItem, SequenceMatchFunction, SequenceMatchResult, TreeView, ValueIndex};
+    use crate::data_types::data_types::{DataTypes, SequenceAttributes};
+    use crate::diagnostics::diagnostics::{Diagnostics, DiagnosticsHook};
+    use crate::errors::errors::CptError;
+    use crate::metrics::metrics::{MetricMatchResult, SimilarityMetric};
+    use crate::nodes::nodes::{Node, NodeId};
+
+    pub type CompactNodeId = u32;
+
+    const ROOT_ID: CompactNodeId = 0;
+    // Child lists up to this length do not allocate
+    const INLINE_CHILDREN: usize = 3;
+
+    // The children of a node, inline up to `INLINE_CHILDREN` children. Takes 16 bytes.
+    #[derive(Debug, Clone)]
+    pub enum ChildList {
+        Inline(u8, [CompactNodeId; INLINE_CHILDREN]),
+        Spilled(Box<Vec<CompactNodeId>>),
+    }
+
+    impl Default for ChildList {
+        fn default() -> Self {
+            ChildList::Inline(0, [ROOT_ID; INLINE_CHILDREN])
+        }
+    }
+
+    impl ChildList {
+        pub fn as_slice(&self) -> &[CompactNodeId] {
+            match self {
+                ChildList::Inline(len, ids) => &ids[..*len as usize],
+                ChildList::Spilled(ids) => ids.as_slice(),
+            }
+        }
+
+        pub fn insert(&mut self, position: usize, id: CompactNodeId) {
+            match self {
+                ChildList::Inline(len, ids) if (*len as usize) < INLINE_CHILDREN => {
+                    ids.copy_within(position..*len as usize, position + 1);
+                    ids[position] = id;
+                    *len += 1;
+                },
+                ChildList::Inline(_, ids) => {
+                    let mut spilled = ids.to_vec();
+                    spilled.insert(position, id);
+                    *self = ChildList::Spilled(Box::new(spilled));
+                },
+                ChildList::Spilled(ids) => ids.insert(position, id),
+            }
+        }
+
+        // Bytes allocated on the heap
+        fn heap_bytes(&self) -> usize {
+            match self {
+                ChildList::Inline(..) => 0,
+                ChildList::Spilled(ids) => size_of::<Vec<CompactNodeId>>() + ids.capacity() * size_of::<CompactNodeId>(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Default)]
+    pub(crate) struct CompactIndex {
+        values: Vec<DataTypes>,
+        node_ids: Vec<Vec<CompactNodeId>>,
+    }
+
+    impl CompactIndex {
+        fn insert_value(&mut self, value: DataTypes, node_id: CompactNodeId) {
+            match self.values.binary_search_by(|&probe| InvertedIndex::element_ordering(probe, value)) {
+                Ok(value_id) => self.node_ids[value_id].push(node_id),
+                Err(value_id) => {
+                    self.values.insert(value_id, value);
+                    self.node_ids.insert(value_id, vec![node_id]);
+                }
+            }
+        }
+    }
+
+    impl ValueIndex for CompactIndex {
+        fn values(&self) -> &[DataTypes] {
+            &self.values
+        }
+
+        fn node_ids_at(&self, position: usize) -> impl ExactSizeIterator<Item = NodeId> + '_ {
+            self.node_ids[position].iter().map(|&node_id| node_id as NodeId)
+        }
+    }
+
+    // Estimated bytes used by a model, including the heap allocations of its collections.
+    #[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct MemoryUsage {
+        // The nodes and their child lists
+        pub nodes: usize,
+        // The inverted index, and the child index of a CPT
+        pub index: usize,
+        // The sequences lookup table and the sequence attributes
+        pub lookup_table: usize,
+    }
+
+    impl MemoryUsage {
+        pub fn total(&self) -> usize {
+            self.nodes + self.index + self.lookup_table
+        }
+    }
+
+    fn hash_map_bytes<K, V>(map: &HashMap<K, V>) -> usize {
+        // Each bucket holds an entry and a control byte
+        map.capacity() * (size_of::<(K, V)>() + 1)
+    }
+
+    fn lookup_table_bytes<K>(table: &HashMap<K, Vec<SequenceAttributes>>) -> usize {
+        hash_map_bytes(table) + table.values().map(|attributes| {
+            attributes.capacity() * size_of::<SequenceAttributes>() + attributes.iter().map(|attribute| match attribute {
+                SequenceAttributes::ClassStr(class) => class.capacity(),
+            }).sum::<usize>()
+        }).sum::<usize>()
+    }
+
+    impl CPT<DataTypes> {
+        // Estimates the memory used by the tree, the indexes and the lookup table.
+        pub fn memory_usage(&self) -> MemoryUsage {
+            MemoryUsage {
+                nodes: self.nodes.capacity() * size_of::<Node<DataTypes>>()
+                    + self.nodes.iter().map(|node| node.children.capacity() * size_of::<NodeId>()).sum::<usize>(),
+                index: self.inverted_index.values.capacity() * size_of::<DataTypes>()
+                    + self.inverted_index.node_ids.capacity() * size_of::<Vec<NodeId>>()
+                    + self.inverted_index.node_ids.iter().map(|node_ids| node_ids.capacity() * size_of::<NodeId>()).sum::<usize>()
+                    + hash_map_bytes(&self.child_index)
+                    + self.child_index.values().map(hash_map_bytes).sum::<usize>(),
+                lookup_table: lookup_table_bytes(&self.sequences_lookup_table),
+            }
+        }
+
+        pub fn compact(&self) -> CompactCPT {
+            self.try_compact().unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        // Copies the tree into a `CompactCPT`, node ids are kept. Fails if the tree
+        // has more nodes than 32-bit ids can address.
+        pub fn try_compact(&self) -> Result<CompactCPT, CptError> {
+            let compact_id = |id: NodeId| CompactNodeId::try_from(id).map_err(|_| CptError::TooManyNodes(self.nodes.len()));
+            compact_id(self.nodes.len())?;
+
+            let sorted_children = |node: &Node<DataTypes>| -> Result<ChildList, CptError> {
+                let mut children = node.children.clone();
+                children.sort_by_key(|&child_id| self.nodes.get(child_id).and_then(|child| child.data));
+                let mut child_list = ChildList::default();
+                for (position, child_id) in children.into_iter().enumerate() {
+                    child_list.insert(position, compact_id(child_id)?);
+                }
+                Ok(child_list)
+            };
+
+            let root = self.nodes.first().ok_or_else(|| CptError::CorruptModel("The tree has no root node".to_string()))?;
+            let mut compact = CompactCPT {
+                root_children: sorted_children(root)?,
+                data: Vec::with_capacity(self.nodes.len() - 1),
+                parents: Vec::with_capacity(self.nodes.len() - 1),
+                children: Vec::with_capacity(self.nodes.len() - 1),
+                index: CompactIndex::default(),
+                sequences_lookup_table: HashMap::with_capacity(self.sequences_lookup_table.len()),
+                items_inserted: self.items_inserted,
+                diagnostics: self.diagnostics.clone(),
+            };
+            for (id, node) in self.nodes.iter().enumerate().skip(1) {
+                compact.data.push(node.data.ok_or_else(|| CptError::CorruptModel(format!("Node {} has no data", id)))?);
+                compact.parents.push(compact_id(node.parent.ok_or_else(|| CptError::CorruptModel(format!("Node {} has no parent", id)))?)?);
+                compact.children.push(sorted_children(node)?);
+            }
+            compact.index = CompactIndex {
+                values: self.inverted_index.values.clone(),
+                node_ids: self.inverted_index.node_ids.iter()
+                    .map(|node_ids| node_ids.iter().map(|&node_id| compact_id(node_id)).collect())
+                    .collect::<Result<Vec<Vec<CompactNodeId>>, CptError>>()?,
+            };
+            for (&last_node_id, attributes) in self.sequences_lookup_table.iter() {
+                compact.sequences_lookup_table.insert(compact_id(last_node_id)?, attributes.clone());
+            }
+            Ok(compact)
+        }
+    }
+
+    // A CPT using less memory per node, see the description of the layout above.
+    // It can be trained and queried like a CPT, and converted back with `to_cpt`.
+    #[derive(Debug, Clone, Default)]
+    pub struct CompactCPT {
+        root_children: ChildList,
+        // Data, parent and children of node i are at position i - 1
+        data: Vec<DataTypes>,
+        parents: Vec<CompactNodeId>,
+        children: Vec<ChildList>,
+        index: CompactIndex,
+        // The lookup table references the last node of each sequence,
+        // with the attributes of the sequence
+        sequences_lookup_table: HashMap<CompactNodeId, Vec<SequenceAttributes>>,
+        // Number of items added, like `CPT::items_inserted`
+        items_inserted: Option<usize>,
+        pub diagnostics: Diagnostics
+    }
+
+    impl CompactCPT {
+        pub fn new() -> CompactCPT {
+            Self::default()
+        }
+
+        // Sends the intermediate state of `match_sequence` and `predict` to the given hook.
+        pub fn set_diagnostics<H: DiagnosticsHook + 'static>(&mut self, hook: H) {
+            self.diagnostics = Diagnostics::new(hook);
+        }
+
+        // Number of nodes in the tree, including the root.
+        pub fn node_count(&self) -> usize {
+            self.data.len() + 1
+        }
+
+        pub fn get_data(&self, id: NodeId) -> Option<DataTypes> {
+            self.node_data(id)
+        }
+
+        pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+            self.node_parent(id)
+        }
+
+        // The children of a node, sorted by data. Unknown nodes have no children.
+        pub fn children(&self, id: NodeId) -> &[CompactNodeId] {
+            self.child_list(id).map(|children| children.as_slice()).unwrap_or(&[])
+        }
+
+        // The attributes of the sequence ending at a node, None if no sequence ends there.
+        pub fn sequence_attributes(&self, last_node_id: NodeId) -> Option<&[SequenceAttributes]> {
+            CompactNodeId::try_from(last_node_id).ok()
+                .and_then(|last_node_id| self.sequences_lookup_table.get(&last_node_id))
+                .map(|attributes| attributes.as_slice())
+        }
+
+        fn child_list(&self, id: NodeId) -> Option<&ChildList> {
+            if id == ROOT_ID as NodeId {
+                Some(&self.root_children)
+            } else {
+                self.children.get(id - 1)
+            }
+        }
+
+        pub fn add_sequence_to_root(&mut self, sequence: Vec<DataTypes>, sequence_attributes: Option<Vec<SequenceAttributes>>) {
+            self.try_add_sequence(&sequence, ROOT_ID as NodeId, sequence_attributes).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        // Adds a sequence to the tree, from the node at node_id, like `CPT::add_sequence`.
+        pub fn try_add_sequence(&mut self, sequence: &[DataTypes], node_id: NodeId, sequence_attributes: Option<Vec<SequenceAttributes>>) -> Result<(), CptError> {
+            let mut current_node_id = CompactNodeId::try_from(node_id).map_err(|_| CptError::UnknownNode(node_id))?;
+            for &item in sequence.iter() {
+                current_node_id = self.add_child(item, current_node_id)?;
+            }
+            if let Some(items_inserted) = self.items_inserted.as_mut() {
+                *items_inserted += sequence.len();
+            }
+
+            // If attributes have been provided, insert them in the attributes table
+            if let Some(sequence_attributes_vec) = sequence_attributes {
+                self.sequences_lookup_table.entry(current_node_id).or_default().extend(sequence_attributes_vec);
+            } else {
+                self.sequences_lookup_table.insert(current_node_id, vec![]);
+            }
+            Ok(())
+        }
+
+        fn add_child(&mut self, new_data: DataTypes, node_id: CompactNodeId) -> Result<CompactNodeId, CptError> {
+            let children = self.child_list(node_id as NodeId).ok_or(CptError::UnknownNode(node_id as NodeId))?.as_slice();
+            match children.binary_search_by(|&child_id| InvertedIndex::element_ordering(self.data[child_id as usize - 1], new_data)) {
+                Ok(position) => Ok(children[position]),
+                Err(position) => {
+                    let new_node_id = CompactNodeId::try_from(self.node_count()).map_err(|_| CptError::TooManyNodes(self.node_count() + 1))?;
+                    self.data.push(new_data);
+                    self.parents.push(node_id);
+                    self.children.push(ChildList::default());
+                    if node_id == ROOT_ID {
+                        self.root_children.insert(position, new_node_id);
+                    } else {
+                        self.children[node_id as usize - 1].insert(position, new_node_id);
+                    }
+                    self.index.insert_value(new_data, new_node_id);
+                    Ok(new_node_id)
+                }
+            }
+        }
+
+        // Copies the tree back into a CPT, node ids are kept.
+        pub fn to_cpt(&self) -> CPT<DataTypes> {
+            let mut cpt = CPT::new();
+            cpt.nodes[CPT::get_root_id()].children = self.root_children.as_slice().iter().map(|&child_id| child_id as NodeId).collect();
+            cpt.nodes.extend(self.data.iter().zip(self.parents.iter()).zip(self.children.iter()).map(|((&data, &parent), children)| Node {
+                parent: Some(parent as NodeId),
+                children: children.as_slice().iter().map(|&child_id| child_id as NodeId).collect(),
+                data: Some(data),
+            }));
+            cpt.inverted_index = InvertedIndex {
+                values: self.index.values.clone(),
+                node_ids: self.index.node_ids.iter()
+                    .map(|node_ids| node_ids.iter().map(|&node_id| node_id as NodeId).collect())
+                    .collect(),
+            };
+            cpt.sequences_lookup_table = self.sequences_lookup_table.iter()
+                .map(|(&last_node_id, attributes)| (last_node_id as NodeId, attributes.clone()))
+                .collect();
+            cpt.items_inserted = self.items_inserted;
+            cpt.diagnostics = self.diagnostics.clone();
+            cpt
+        }
+
+        // Estimates the memory used by the tree, the index and the lookup table.
+        pub fn memory_usage(&self) -> MemoryUsage {
+            MemoryUsage {
+                nodes: size_of::<ChildList>() + self.root_children.heap_bytes()
+                    + self.data.capacity() * size_of::<DataTypes>()
+                    + self.parents.capacity() * size_of::<CompactNodeId>()
+                    + self.children.capacity() * size_of::<ChildList>()
+                    + self.children.iter().map(ChildList::heap_bytes).sum::<usize>(),
+                index: self.index.values.capacity() * size_of::<DataTypes>()
+                    + self.index.node_ids.capacity() * size_of::<Vec<CompactNodeId>>()
+                    + self.index.node_ids.iter().map(|node_ids| node_ids.capacity() * size_of::<CompactNodeId>()).sum::<usize>(),
+                lookup_table: lookup_table_bytes(&self.sequences_lookup_table),
+            }
+        }
+
+        pub fn match_sequence(&self, sequence: &[DataTypes], backwards: bool, match_functions: &[SequenceMatchFunction]) -> Vec<SequenceMatchResult> {
+            self.match_sequence_with_options(sequence, backwards, match_functions, &MatchOptions::default()).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        pub fn match_sequence_with_options(&self, sequence: &[DataTypes], backwards: bool, match_functions: &[SequenceMatchFunction], options: &MatchOptions) -> Result<Vec<SequenceMatchResult>, CptError> {
+            TreeView::match_sequence(self, sequence, backwards, match_functions, options)
+        }
+
+        pub fn match_sequence_with_metrics(&self, sequence: &[DataTypes], backwards: bool, metrics: &[&dyn SimilarityMetric], options: &MatchOptions) -> Result<Vec<MetricMatchResult>, CptError> {
+            TreeView::match_sequence_with_metrics(self, sequence, backwards, metrics, options)
+        }
+
+        pub fn predict(&self, sequence: &[DataTypes], prefix_length: usize) -> Vec<PredictedItem> {
+            self.try_predict(sequence, prefix_length).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        pub fn try_predict(&self, sequence: &[DataTypes], prefix_length: usize) -> Result<Vec<PredictedItem>, CptError> {
+            TreeView::try_predict(self, sequence, prefix_length)
+        }
+    }
+
+    impl TreeView for CompactCPT {
+        type Index = CompactIndex;
+
+        fn index(&self) -> &CompactIndex {
+            &self.index
+        }
+
+        fn node_data(&self, id: NodeId) -> Option<DataTypes> {
+            id.checked_sub(1).and_then(|position| self.data.get(position)).copied()
+        }
+
+        fn node_parent(&self, id: NodeId) -> Option<NodeId> {
+            id.checked_sub(1).and_then(|position| self.parents.get(position)).map(|&parent| parent as NodeId)
+        }
+
+        fn node_children(&self, id: NodeId) -> Option<impl Iterator<Item = NodeId> + '_> {
+            self.child_list(id).map(|children| children.as_slice().iter().map(|&child_id| child_id as NodeId))
+        }
+
+        fn is_sequence_end(&self, id: NodeId) -> bool {
+            CompactNodeId::try_from(id).is_ok_and(|id| self.sequences_lookup_table.contains_key(&id))
+        }
+
+        fn diagnostics(&self) -> &Diagnostics {
+            &self.diagnostics
+        }
+    }
+}
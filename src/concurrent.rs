@@ -0,0 +1,101 @@
+pub mod concurrent {
+    use std::sync::{Arc, Mutex, RwLock};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use crate::cpt::cpt::{CPT, MatchOptions, PredictedItem, SequenceMatchFunction, SequenceMatchResult, TrainingSequence};
+    use crate::data_types::data_types::{DataTypes, SequenceAttributes};
+    use crate::errors::errors::CptError;
+
+    struct Writer {
+        // The other copy of the model, published before the current one. It is caught up and
+        // published in turn, so that the published tree is never modified nor copied
+        standby: Arc<CPT<DataTypes>>,
+        // Sequences of the published version that the standby copy does not have yet
+        behind: Vec<TrainingSequence>,
+        // Sequences added since the last publication
+        pending: Vec<TrainingSequence>
+    }
+
+    // A CPT that many threads can query while one or more threads keep training it.
+    // Readers work on immutable snapshots, and new sequences are published every
+    // `publish_every` sequences, or when `publish` is called.
+    // Two copies of the tree are kept and published in turn: a publication only adds to
+    // the standby copy the sequences it misses, so its cost depends on the number of new
+    // sequences, not on the size of the model. The standby copy is only cloned when a
+    // snapshot of it is still held, that is a snapshot taken two publications earlier.
+    pub struct SharedCPT {
+        published: RwLock<Arc<CPT<DataTypes>>>,
+        writer: Mutex<Writer>,
+        publish_every: usize,
+        version: AtomicU64
+    }
+
+    impl SharedCPT {
+        // Wraps a trained or empty CPT, which is copied once for the standby version. With
+        // `publish_every` set to 0, new sequences are only visible to readers after a call to `publish`.
+        pub fn new(cpt: CPT<DataTypes>, publish_every: usize) -> SharedCPT {
+            SharedCPT {
+                writer: Mutex::new(Writer { standby: Arc::new(cpt.clone()), behind: vec![], pending: vec![] }),
+                published: RwLock::new(Arc::new(cpt)),
+                publish_every,
+                version: AtomicU64::new(0)
+            }
+        }
+
+        // Returns the latest published version of the model.
+        pub fn snapshot(&self) -> Arc<CPT<DataTypes>> {
+            self.published.read().unwrap_or_else(|e| e.into_inner()).clone()
+        }
+
+        // Number of publications so far.
+        pub fn version(&self) -> u64 {
+            self.version.load(Ordering::Acquire)
+        }
+
+        // Adds a training sequence, and publishes the model if enough sequences are pending.
+        pub fn add_sequence(&self, sequence: Vec<DataTypes>, sequence_attributes: Option<Vec<SequenceAttributes>>) {
+            let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+            writer.pending.push((sequence, sequence_attributes));
+            if self.publish_every > 0 && writer.pending.len() >= self.publish_every {
+                self.publish_locked(&mut writer);
+            }
+        }
+
+        // Makes every sequence added so far visible to the readers.
+        pub fn publish(&self) {
+            let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+            self.publish_locked(&mut writer);
+        }
+
+        fn publish_locked(&self, writer: &mut Writer) {
+            if writer.pending.is_empty() {
+                return;
+            }
+            let Writer { standby, behind, pending } = writer;
+            // Only copied if a reader still holds the standby version
+            let model = Arc::make_mut(standby);
+            behind.drain(..).chain(pending.iter().cloned())
+                .for_each(|(sequence, sequence_attributes)| model.add_sequence_to_root(sequence, sequence_attributes));
+            let previous = std::mem::replace(&mut *self.published.write().unwrap_or_else(|e| e.into_inner()), standby.clone());
+            *standby = previous;
+            // The previous version misses the sequences just published
+            std::mem::swap(behind, pending);
+            self.version.fetch_add(1, Ordering::AcqRel);
+        }
+
+        // `CPT::try_predict` on the latest published version.
+        pub fn predict(&self, sequence: &[DataTypes], prefix_length: usize) -> Result<Vec<PredictedItem>, CptError> {
+            self.snapshot().try_predict(sequence, prefix_length)
+        }
+
+        // `CPT::match_sequence` on the latest published version.
+        pub fn match_sequence(&self, sequence: &[DataTypes], backwards: bool, match_functions: &[SequenceMatchFunction]) -> Vec<SequenceMatchResult> {
+            self.snapshot().match_sequence(sequence, backwards, match_functions)
+        }
+
+        // `CPT::match_sequence_with_options` on the latest published version.
+        pub fn match_sequence_with_options(&self, sequence: &[DataTypes], backwards: bool, match_functions: &[SequenceMatchFunction], options: &MatchOptions) -> Result<Vec<SequenceMatchResult>, CptError> {
+            self.snapshot().match_sequence_with_options(sequence, backwards, match_functions, options)
+        }
+    }
+}
@@ -3,29 +3,91 @@ pub mod cpt{
     pub type Scores = HashMap::<SequenceMatchFunction,SimilarityScores>;
     pub type NodeMatchResult = (NodeId, Vec<(SequenceMatchFunction, SimilarityScores)>);
     pub type SequenceMatchResult = (Vec<NodeMatchResult>, Scores);
+    // A predicted item, with its support and confidence
+    pub type PredictedItem = (DataTypes, usize, f32);
+    // The consequent nodes used by a prediction, with the last node of their training sequence
+    pub(crate) type Consequent = (NodeId, Vec<NodeId>);
+    // A sequence to train on, with its optional attributes
+    pub type TrainingSequence = (Vec<DataTypes>, Option<Vec<SequenceAttributes>>);
 
     #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
     pub enum SequenceMatchFunction{
         StrictEqual,
         AlgebraicDistance,
-        SequenceLength
+        // Scores 1 per matched item, so a match scores its length: matches of the same length tie,
+        // and are ranked by the next match function, then by node ids
+        SequenceLength,
+        // The following metrics compare the whole query with a matched sequence, see the alignment module
+        EditDistance(EditCosts),
+        LongestCommonSubsequence,
+        DynamicTimeWarping
     }
 
-    #[derive(Clone, Copy)]
+    impl SequenceMatchFunction {
+        // Whether the function scores whole sequences rather than single items.
+        pub fn is_sequence_metric(self) -> bool {
+            matches!(self, SequenceMatchFunction::EditDistance(_) | SequenceMatchFunction::LongestCommonSubsequence | SequenceMatchFunction::DynamicTimeWarping)
+        }
+    }
+
+    // How the candidate nodes for the first item of a match are picked in the inverted index
+    #[derive(Clone, Copy, Debug, PartialEq)]
     pub enum SequenceRetreiveFunction{
-        TopNSimilarValues(usize)
+        // Every node of the tree, scored with each match function, except for StrictEqual alone
+        AllValues,
+        // The nodes of the N values closest to the query value
+        TopNSimilarValues(usize),
+        // The nodes of the values at most this distance away from the query value
+        WithinDistance(f32),
+        // The nodes holding the query value
+        ExactOnly
+    }
+
+    // Options of `match_sequence_with_options`. The default options match `match_sequence`.
+    #[derive(Clone, Debug)]
+    pub struct MatchOptions{
+        // Picks the candidate nodes for the first item matched
+        pub retrieve_function: SequenceRetreiveFunction,
+        // Keeps only the best matches
+        pub max_results: Option<usize>,
+        // Orders the matches when several match functions are used
+        pub ranking: RankingPolicy,
+        // Forward matches only extend this many partial matches at each item, the best ones
+        pub beam_width: Option<usize>,
+        // Forward matches are dropped as soon as their total score on the first match function is worse
+        pub score_threshold: Option<f32>,
+        // Number of nodes that may be skipped between two matched items, 0 for contiguous matches
+        pub max_gap: usize,
+        // Added to the cost, or removed from the reward, of a matched node for each node skipped before it.
+        // Lengths and equality cannot be penalized, a skip with a penalty is an error for them
+        pub skip_penalty: f32,
+        // Sequence metrics compare the query with the training sequence read up from the last matched node,
+        // over the length of the query and this many more items, so that shifted sequences still align
+        pub alignment_margin: usize
+    }
+
+    impl Default for MatchOptions {
+        fn default() -> Self {
+            MatchOptions { retrieve_function: SequenceRetreiveFunction::AllValues, max_results: None, ranking: RankingPolicy::default(), beam_width: None, score_threshold: None, max_gap: 0, skip_penalty: 0.0, alignment_margin: 0 }
+        }
     }
 
+    use std::cell::RefCell;
     use std::cmp::Ordering;
+    use crate::alignment::alignment::{EditCosts, sequence_similarity};
     use crate::data_types::data_types::{DataTypes, SequenceAttributes};
     // use crate::data_types::data_types::Scores as Scores;
     use crate::data_types::data_types::SimilarityScores as SimilarityScores;
     use crate::nodes::nodes::{Node, NodeId};
+    use crate::errors::errors::{CptError, keep_first_error};
+    use crate::diagnostics::diagnostics::{Diagnostics, DiagnosticEvent, DiagnosticsHook};
+    use crate::metrics::metrics::{MetricMatchResult, ScoreKind, SimilarityMetric};
+    use crate::ranking::ranking::{RankingPolicy, rank};
 
-    #[derive(Serialize, Deserialize, Debug)]
+    #[derive(Serialize, Deserialize, Debug, Clone)]
     pub struct InvertedIndex<T>{
-        values: Vec<T>,
-        node_ids: Vec<Vec<NodeId>>,
+        pub(crate) values: Vec<T>,
+        pub(crate) node_ids: Vec<Vec<NodeId>>,
     }
 
     impl<T> InvertedIndex<T>{
@@ -55,10 +117,14 @@ pub mod cpt{
         }
 
         pub fn element_matching(match_function: SequenceMatchFunction, a: DataTypes, b: DataTypes) -> SimilarityScores {
+            InvertedIndex::try_element_matching(match_function, a, b).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        pub fn try_element_matching(match_function: SequenceMatchFunction, a: DataTypes, b: DataTypes) -> Result<SimilarityScores, CptError> {
             // Function to match two values, used to search a value in the graph.
             // Currently set up to test equality, this could potentially
             // Be changed to another heuristic
-            a.compute_similarity(match_function, b)
+            a.try_compute_similarity(match_function, b)
         }
 
         pub fn get_value_ids(&self, value: DataTypes) -> Option<&Vec<NodeId>> {
@@ -81,18 +147,76 @@ pub mod cpt{
         }
 
         pub fn get_similar_value_ids(&self, sequence_match_functions: &[SequenceMatchFunction], value: DataTypes) -> HashMap::<NodeId, Vec<(SequenceMatchFunction, SimilarityScores)>> {
+            self.try_get_similar_value_ids(sequence_match_functions, value).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        pub fn try_get_similar_value_ids(&self, sequence_match_functions: &[SequenceMatchFunction], value: DataTypes) -> Result<HashMap::<NodeId, Vec<(SequenceMatchFunction, SimilarityScores)>>, CptError> {
+            ValueIndex::similar_value_ids(self, sequence_match_functions, value)
+        }
+
+        // Returns the values at most `radius` away from the given value, closest first, with their distance.
+        pub fn get_values_within(&self, value: DataTypes, radius: f32) -> Result<Vec<(DataTypes, f32)>, CptError> {
+            ValueIndex::values_within(self, value, radius)
+        }
+
+        // Returns the `count` values closest to the given value, closest first, with their distance.
+        pub fn get_closest_values(&self, value: DataTypes, count: usize) -> Result<Vec<(DataTypes, f32)>, CptError> {
+            ValueIndex::closest_values(self, value, count)
+        }
+
+        // Like `get_similar_value_ids`, with user defined metrics. Scores are in the order of the metrics.
+        pub fn get_similar_value_ids_with_metrics(&self, metrics: &[&dyn SimilarityMetric], value: DataTypes) -> Result<HashMap<NodeId, Vec<f32>>, CptError> {
+            ValueIndex::metric_value_ids(self, metrics, value, SequenceRetreiveFunction::AllValues)
+        }
+
+        // Like `get_similar_value_ids`, but only the nodes of the values picked by the retrieve function are scored.
+        pub fn get_retrieved_value_ids(&self, sequence_match_functions: &[SequenceMatchFunction], value: DataTypes, retrieve_function: SequenceRetreiveFunction) -> Result<HashMap::<NodeId, Vec<(SequenceMatchFunction, SimilarityScores)>>, CptError> {
+            ValueIndex::retrieved_value_ids(self, sequence_match_functions, value, retrieve_function)
+        }
+
+        fn insert_value(&mut self, value: DataTypes, node_id: NodeId){
+            // Check whether the value exists in the index
+            // If the value already exists, just add the node_id
+            // in the list of node_ids associated to this value
+            match self.values.binary_search_by(|&probe| InvertedIndex::element_ordering(probe, value) ) {
+                Ok(value_id) => self.node_ids[value_id].push(node_id), // element already in vector
+                Err(value_id) => {
+                    // If it doesn't exist create a new entry for this value
+                        self.values.insert(value_id, value);
+                        self.node_ids.insert(value_id, [node_id].to_vec());
+                    }
+            }
+        }
+    }
+
+    // Read access to the sorted values of a tree and the nodes holding them,
+    // shared by `InvertedIndex` and the index of `FrozenCPT`.
+    pub(crate) trait ValueIndex {
+        // The unique values of the tree, sorted
+        fn values(&self) -> &[DataTypes];
+        // The ids of the nodes holding the value at a position of values()
+        fn node_ids_at(&self, position: usize) -> impl ExactSizeIterator<Item = NodeId> + '_;
+
+        fn value_node_ids(&self, value: DataTypes) -> Option<impl ExactSizeIterator<Item = NodeId> + '_> {
+            self.values().binary_search_by(|&probe| InvertedIndex::element_ordering(probe, value))
+                .ok()
+                .map(|position| self.node_ids_at(position))
+        }
+
+        fn similar_value_ids(&self, sequence_match_functions: &[SequenceMatchFunction], value: DataTypes) -> Result<HashMap::<NodeId, Vec<(SequenceMatchFunction, SimilarityScores)>>, CptError> {
             // For a given value, return the node ids and the value of the similarity scores
             // It will look into the tree for values matching the input values, using the sequence_match_functions
             // Each similarity score for each matching node id will be returned in a hashmap:
             // { NodeId: [SimilarityScore(), SimilarityScore()], ... }
             let mut similarities = HashMap::<NodeId, Vec<(SequenceMatchFunction, SimilarityScores)>>::new();
 
-            sequence_match_functions.iter().for_each(|&sequence_match_function|{
+            // Sequence metrics do not score items, they are computed on the matched sequences
+            for &sequence_match_function in sequence_match_functions.iter().filter(|function| !function.is_sequence_metric()) {
                 // If we only look for equal value, take advantage of binary search
                 match sequence_match_function {
                     SequenceMatchFunction::StrictEqual => {
-                        if let Some(node_ids) = self.get_value_ids(value){
-                            node_ids.iter().for_each(|&node_id| {
+                        if let Some(node_ids) = self.value_node_ids(value){
+                            node_ids.for_each(|node_id| {
                                 similarities.entry(node_id).or_insert(
                                     vec![] 
                                 ).push( (SequenceMatchFunction::StrictEqual, SimilarityScores::IsEqual(true)) );
@@ -101,49 +225,168 @@ pub mod cpt{
                     }
                     // If we only look for almost equal value, scan all values: this could probably be optimized
                     _ => {
-                        self.values.iter().for_each(|&probe| {
-                            self.get_value_ids(probe).unwrap().into_iter().for_each(|&node_id| {
-                                let similarity = (sequence_match_function, InvertedIndex::element_matching(sequence_match_function, probe, value));
+                        for (position, &probe) in self.values().iter().enumerate() {
+                            let similarity = (sequence_match_function, InvertedIndex::try_element_matching(sequence_match_function, probe, value)?);
+                            self.node_ids_at(position).for_each(|node_id| {
                                 similarities.entry(node_id).or_insert(
                                     vec![] 
                                 ).push( similarity );
                             });
-                        });
+                        }
                     },
                 }
-            });
-            similarities
+            }
+            // Without item scores, every node can start a match
+            if sequence_match_functions.iter().all(|function| function.is_sequence_metric()) {
+                (0..self.values().len()).for_each(|position| {
+                    self.node_ids_at(position).for_each(|node_id| { similarities.insert(node_id, vec![]); });
+                });
+            }
+            Ok(similarities)
         }
 
-        fn insert_value(&mut self, value: DataTypes, node_id: NodeId){
-            // Check whether the value exists in the index
-            // If the value already exists, just add the node_id
-            // in the list of node_ids associated to this value
-            match self.values.binary_search_by(|&probe| InvertedIndex::element_ordering(probe, value) ) {
-                Ok(value_id) => self.node_ids[value_id].push(node_id), // element already in vector
-                Err(value_id) => {
-                    // If it doesn't exist create a new entry for this value
-                        self.values.insert(value_id, value);
-                        self.node_ids.insert(value_id, [node_id].to_vec());
-                    }
+        fn values_within(&self, value: DataTypes, radius: f32) -> Result<Vec<(DataTypes, f32)>, CptError> {
+            Ok(self.closest_value_positions(value, Some(radius), None)?.into_iter()
+                .map(|(position, distance)| (self.values()[position], distance))
+                .collect())
+        }
+
+        fn closest_values(&self, value: DataTypes, count: usize) -> Result<Vec<(DataTypes, f32)>, CptError> {
+            Ok(self.closest_value_positions(value, None, Some(count))?.into_iter()
+                .map(|(position, distance)| (self.values()[position], distance))
+                .collect())
+        }
+
+        fn retrieved_value_ids(&self, sequence_match_functions: &[SequenceMatchFunction], value: DataTypes, retrieve_function: SequenceRetreiveFunction) -> Result<HashMap::<NodeId, Vec<(SequenceMatchFunction, SimilarityScores)>>, CptError> {
+            if retrieve_function == SequenceRetreiveFunction::AllValues {
+                return self.similar_value_ids(sequence_match_functions, value);
+            }
+            let mut similarities = HashMap::<NodeId, Vec<(SequenceMatchFunction, SimilarityScores)>>::new();
+            for position in self.retrieved_positions(value, retrieve_function)? {
+                let probe = self.values()[position];
+                // Each retrieved value is scored once, for all the nodes holding it
+                let scores = sequence_match_functions.iter().filter(|function| !function.is_sequence_metric()).map(|&sequence_match_function| {
+                    let similarity = match sequence_match_function {
+                        // The retrieved values are not all equal to the query value
+                        SequenceMatchFunction::StrictEqual => SimilarityScores::IsEqual(probe == value),
+                        _ => InvertedIndex::try_element_matching(sequence_match_function, probe, value)?
+                    };
+                    Ok((sequence_match_function, similarity))
+                }).collect::<Result<Vec<(SequenceMatchFunction, SimilarityScores)>, CptError>>()?;
+                self.node_ids_at(position).for_each(|node_id| { similarities.entry(node_id).or_default().extend(scores.iter().copied()); });
             }
+            Ok(similarities)
+        }
+
+        fn metric_value_ids(&self, metrics: &[&dyn SimilarityMetric], value: DataTypes, retrieve_function: SequenceRetreiveFunction) -> Result<HashMap<NodeId, Vec<f32>>, CptError> {
+            // Scores each retrieved value once, for all the nodes holding it
+            let mut similarities = HashMap::<NodeId, Vec<f32>>::new();
+            for position in self.retrieved_positions(value, retrieve_function)? {
+                let probe = self.values()[position];
+                let scores = metrics.iter().map(|metric| metric.score(probe, value)).collect::<Result<Vec<f32>, CptError>>()?;
+                self.node_ids_at(position).for_each(|node_id| { similarities.insert(node_id, scores.clone()); });
+            }
+            Ok(similarities)
+        }
+
+        fn retrieved_positions(&self, value: DataTypes, retrieve_function: SequenceRetreiveFunction) -> Result<Vec<usize>, CptError> {
+            // Positions in values() of the values picked by the retrieve function
+            Ok(match retrieve_function {
+                SequenceRetreiveFunction::AllValues => (0..self.values().len()).collect(),
+                SequenceRetreiveFunction::TopNSimilarValues(count) => self.closest_value_positions(value, None, Some(count))?
+                    .into_iter().map(|(position, _distance)| position).collect(),
+                SequenceRetreiveFunction::WithinDistance(radius) => self.closest_value_positions(value, Some(radius), None)?
+                    .into_iter().map(|(position, _distance)| position).collect(),
+                SequenceRetreiveFunction::ExactOnly => self.values().binary_search_by(|&probe| InvertedIndex::element_ordering(probe, value))
+                    .map(|position| vec![position])
+                    .unwrap_or_default(),
+            })
+        }
+
+        fn closest_value_positions(&self, value: DataTypes, radius: Option<f32>, count: Option<usize>) -> Result<Vec<(usize, f32)>, CptError> {
+            // Positions in values() of the values closest to the query value, closest first.
+            // Values are sorted, so the closest values are found by growing a window
+            // around the position of the query value, towards its closest neighbour.
+            // Values of another type are sorted apart and have no distance, the window stops at them.
+            value.try_compute_similarity(SequenceMatchFunction::AlgebraicDistance, value)?;
+            let values = self.values();
+            let distance = |position: usize| match values[position].try_compute_similarity(SequenceMatchFunction::AlgebraicDistance, value) {
+                Ok(SimilarityScores::Distance(distance)) => Some(distance),
+                _ => None
+            };
+            let start = values.binary_search_by(|&probe| InvertedIndex::element_ordering(probe, value)).unwrap_or_else(|position| position);
+            // The window is values[left..right]
+            let (mut left, mut right) = (start, start);
+            let count = count.unwrap_or(values.len());
+            let mut positions = Vec::<(usize, f32)>::new();
+            while positions.len() < count {
+                let left_distance = if left > 0 { distance(left - 1) } else { None };
+                let right_distance = if right < values.len() { distance(right) } else { None };
+                let closest = match (left_distance, right_distance) {
+                    (Some(left_distance), Some(right_distance)) if left_distance <= right_distance => {
+                        left -= 1;
+                        (left, left_distance)
+                    },
+                    (_, Some(right_distance)) => {
+                        right += 1;
+                        (right - 1, right_distance)
+                    },
+                    (Some(left_distance), None) => {
+                        left -= 1;
+                        (left, left_distance)
+                    },
+                    (None, None) => break
+                };
+                if radius.is_some_and(|radius| closest.1 > radius) {
+                    break;
+                }
+                positions.push(closest);
+            }
+            Ok(positions)
+        }
+    }
+
+    impl ValueIndex for InvertedIndex<DataTypes> {
+        fn values(&self) -> &[DataTypes] {
+            &self.values
+        }
+
+        fn node_ids_at(&self, position: usize) -> impl ExactSizeIterator<Item = NodeId> + '_ {
+            self.node_ids[position].iter().copied()
         }
     }
 
     use serde::{Serialize, Deserialize};
     use std::collections::HashMap;
 
-    #[derive(Serialize, Deserialize, Debug)]
+    #[derive(Serialize, Deserialize, Debug, Clone)]
     pub struct CPT<T> {
         // This class is the CPT: it consists of three data structures:
-        // The inverted index is used to match node data values to node ids
+        // The inverted index is used to match node data values to node ids,
+        // it can be left out of saved models as it is rebuilt when loading them
+        #[serde(default = "InvertedIndex::new")]
         pub inverted_index: InvertedIndex<T>,
         // List of nodes
         pub nodes: Vec<Node<T>>,
         // The lookup table references the last node of each sequence
         // In addition to Sequence Attributes for each sequence
-        pub sequences_lookup_table: HashMap<NodeId, Vec<SequenceAttributes>>
+        pub sequences_lookup_table: HashMap<NodeId, Vec<SequenceAttributes>>,
+        // Number of items added by add_sequence, the items of identical sequences included.
+        // It is not saved with the model, so it is unknown (None) for loaded models
+        #[serde(skip)]
+        pub items_inserted: Option<usize>,
+        #[serde(skip)]
+        pub diagnostics: Diagnostics,
+        // Children of the high fan-out nodes by data, so that add_child does not scan them.
+        // It is not saved with the model, the index of a node is built again when it is needed
+        #[serde(skip, default = "HashMap::new")]
+        pub(crate) child_index: HashMap<NodeId, HashMap<T, NodeId>>
     }
+
+    // Nodes with at least this many children are looked up in the child index,
+    // smaller nodes are scanned
+    const CHILD_INDEX_MIN_FAN_OUT: usize = 16;
+
     impl<'a, T> Default for CPT<T> {
         fn default() -> Self {
             let mut nodes = Vec::new();
@@ -151,7 +394,10 @@ pub mod cpt{
             Self {
                 nodes: nodes,
                 inverted_index: InvertedIndex::new(),
-                sequences_lookup_table: HashMap::<NodeId, Vec<SequenceAttributes>>::new()
+                sequences_lookup_table: HashMap::<NodeId, Vec<SequenceAttributes>>::new(),
+                items_inserted: Some(0),
+                diagnostics: Diagnostics::default(),
+                child_index: HashMap::new()
             }
         }
     }
@@ -160,6 +406,11 @@ pub mod cpt{
             Self::default()
         }
 
+        // Sends the intermediate state of `match_sequence` and `predict` to the given hook.
+        pub fn set_diagnostics<H: DiagnosticsHook + 'static>(&mut self, hook: H) {
+            self.diagnostics = Diagnostics::new(hook);
+        }
+
         pub fn to_json(&self) -> String {
             serde_json::to_string(self).unwrap()
         }
@@ -168,6 +419,92 @@ pub mod cpt{
             serde_json::to_string_pretty(self).unwrap()
         }
 
+        // Rebuilds a CPT from the output of `to_json`, see `validate` for the checks performed.
+        pub fn from_json(json: &str) -> Result<CPT<DataTypes>, CptError> {
+            let mut cpt: CPT<DataTypes> = serde_json::from_str(json)?;
+            cpt.validate()?;
+            Ok(cpt)
+        }
+
+        // Checks that the tree is consistent: the root is the first node, parents and
+        // children point to each other, every node is reached once from the root,
+        // and sequences point to existing nodes.
+        // The inverted index is rebuilt if it is missing or does not match the tree.
+        pub fn validate(&mut self) -> Result<(), CptError> {
+            self.check_tree()?;
+            if !self.inverted_index_is_consistent() {
+                self.rebuild_inverted_index();
+            }
+            // The nodes may have been edited directly, the child index is built again on demand
+            self.child_index.clear();
+            Ok(())
+        }
+
+        fn check_tree(&self) -> Result<(), CptError> {
+            // The checks of validate that do not modify the model
+            match self.nodes.first() {
+                Some(root) if root.parent.is_none() && root.data.is_none() => (),
+                _ => return Err(CptError::CorruptModel("The first node is not a root node".to_string()))
+            }
+            for (id, node) in self.nodes.iter().enumerate() {
+                if id != CPT::get_root_id() {
+                    if node.data.is_none() {
+                        return Err(CptError::CorruptModel(format!("Node {} has no data", id)));
+                    }
+                    match node.parent.and_then(|parent_id| self.nodes.get(parent_id)) {
+                        Some(parent) if parent.children.contains(&id) => (),
+                        _ => return Err(CptError::CorruptModel(format!("Node {} is not a child of its parent {:?}", id, node.parent)))
+                    }
+                }
+                for &child_id in node.children.iter() {
+                    if self.nodes.get(child_id).and_then(|child| child.parent) != Some(id) {
+                        return Err(CptError::CorruptModel(format!("Child {} of node {} does not point back to it", child_id, id)));
+                    }
+                }
+            }
+            // Every node must be reached once from the root, so that the parent walks and the
+            // descents of match_sequence and predict end: this rejects cycles and repeated children
+            let mut visited = vec![false; self.nodes.len()];
+            let mut stack = vec![CPT::get_root_id()];
+            while let Some(id) = stack.pop() {
+                if std::mem::replace(&mut visited[id], true) {
+                    return Err(CptError::CorruptModel(format!("Node {} is reached twice from the root", id)));
+                }
+                stack.extend(self.nodes[id].children.iter().copied());
+            }
+            if let Some(id) = visited.iter().position(|&reached| !reached) {
+                return Err(CptError::CorruptModel(format!("Node {} cannot be reached from the root", id)));
+            }
+            if let Some(last_node_id) = self.sequences_lookup_table.keys().find(|&&last_node_id| last_node_id >= self.nodes.len()) {
+                return Err(CptError::CorruptModel(format!("Sequence ends at unknown node {}", last_node_id)));
+            }
+            Ok(())
+        }
+
+        // Whether every node is referenced once in the inverted index, under its own value.
+        pub fn inverted_index_is_consistent(&self) -> bool {
+            let index = &self.inverted_index;
+            index.values.len() == index.node_ids.len()
+                && index.values.windows(2).all(|pair| pair[0] < pair[1])
+                && index.node_ids.iter().map(|node_ids| node_ids.len()).sum::<usize>() + 1 == self.nodes.len()
+                && index.values.iter().zip(index.node_ids.iter()).all(|(&value, node_ids)| {
+                    node_ids.iter().all(|&node_id| {
+                        node_id != CPT::get_root_id() && self.nodes.get(node_id).and_then(|node| node.data) == Some(value)
+                    })
+                })
+        }
+
+        // Rebuilds the inverted index from the nodes of the tree.
+        pub fn rebuild_inverted_index(&mut self) {
+            let mut inverted_index = InvertedIndex::new();
+            for (id, node) in self.nodes.iter().enumerate().skip(1) {
+                if let Some(data) = node.data {
+                    inverted_index.insert_value(data, id);
+                }
+            }
+            self.inverted_index = inverted_index;
+        }
+
         pub fn to_dot(&self) -> String{
             // Write the CPT as a .dot file, Graphviz renders it pretty well
             let mut dot_string = String::from("digraph  Result { \n");
@@ -221,36 +558,59 @@ pub mod cpt{
         }
 
         pub fn update_node(& mut self, id: NodeId, new_node: Node<DataTypes>) {
+            self.try_update_node(id, new_node).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        pub fn try_update_node(& mut self, id: NodeId, new_node: Node<DataTypes>) -> Result<(), CptError> {
             // println!("Updating node {:?}:", id);
-            // println!("-- Before: {:?}", self.nodes.get(id.index0()));
-            self.nodes[id] = new_node;
-            // println!("-- After: {:?}", self.nodes.get(id.index0()));
+            let node = self.nodes.get_mut(id).ok_or(CptError::UnknownNode(id))?;
+            *node = new_node;
+            // The children may have changed, the node will be indexed again if needed
+            self.child_index.remove(&id);
+            Ok(())
         }
 
         pub fn get_data(&self, id: NodeId) -> Option<DataTypes> {
-            *self.get(id).expect(&format!("No node found for NodeId {:?}", id)).get()
+            self.try_get_data(id).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        pub fn try_get_data(&self, id: NodeId) -> Result<Option<DataTypes>, CptError> {
+            self.get(id).map(|node| *node.get()).ok_or(CptError::UnknownNode(id))
+        }
+
+        // Returns the values of the nodes from the root down to the given node, like the training sequence ending there.
+        pub fn get_sequence(&self, last_node_id: NodeId) -> Vec<DataTypes> {
+            self.try_get_sequence(last_node_id).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        pub fn try_get_sequence(&self, last_node_id: NodeId) -> Result<Vec<DataTypes>, CptError> {
+            let mut sequence = Vec::<DataTypes>::new();
+            let mut current_node = self.get(last_node_id).ok_or(CptError::UnknownNode(last_node_id))?;
+            while let (Some(data), Some(parent_node_id)) = (current_node.data, current_node.parent) {
+                sequence.push(data);
+                current_node = self.get(parent_node_id).ok_or(CptError::UnknownNode(parent_node_id))?;
+            }
+            sequence.reverse();
+            Ok(sequence)
         }
 
         pub fn child_exists(&self, new_data: DataTypes, node_id: NodeId) -> Option<NodeId>
             where DataTypes: PartialEq<DataTypes> + Copy {
-
-            let mut matched_node_id = None;
-            // println!("Does child with value {:?} exists for node {:?}", new_data, node_id);
-            if let Some(parent_node) = self.get(node_id){
-                // exists = parent_node.children.as_slice().iter().any(|&id| self.get_data(id) == Some(&new_data))
-                // exists = parent_node.children.as_slice().iter().map(|&id| self.get(id)).any(|node| node.expect("").data == Some(new_data))
-                for child_id in parent_node.children.as_slice(){
-                    // println!("-- Looking for data in child {:?}", child_id);
-                    if InvertedIndex::insert_element_matching(self.get_data(*child_id).unwrap(), new_data){
-                        matched_node_id = Some(child_id.clone());
-                    }
-                }
+            // High fan-out nodes have their children indexed by data
+            if let Some(children_by_data) = self.child_index.get(&node_id) {
+                return children_by_data.get(&new_data).copied();
             }
-            matched_node_id
+            // println!("Does child with value {:?} exists for node {:?}", new_data, node_id);
+            self.get(node_id).and_then(|parent_node| {
+                parent_node.children.iter().copied().find(|&child_id| {
+                    InvertedIndex::insert_element_matching(self.get_data(child_id).unwrap(), new_data)
+                })
+            })
         }
 
         pub fn add_child(&mut self, new_data: DataTypes, node_id: NodeId)-> NodeId where DataTypes: PartialEq<DataTypes> + Copy {
             // println!("Adding value {:?} to CPT at node {:?}", new_data, node_id);
+            self.index_children(node_id);
             match self.child_exists(new_data, node_id)  {
                 // If no child exists with the current new data, create a new node
                 None => {
@@ -258,10 +618,11 @@ pub mod cpt{
                     let new_node_id = self.new_node(new_node);
 
                     // Now update the parent node with a new child
-                    if let Some(parent_node) = self.get(node_id){
-                        let mut new_parent_node = parent_node.clone();
-                        new_parent_node.children.push(new_node_id);
-                        self.update_node(node_id, new_parent_node);
+                    if let Some(parent_node) = self.nodes.get_mut(node_id){
+                        parent_node.children.push(new_node_id);
+                        if let Some(children_by_data) = self.child_index.get_mut(&node_id) {
+                            children_by_data.insert(new_data, new_node_id);
+                        }
                     }
                     new_node_id
                 },
@@ -273,6 +634,19 @@ pub mod cpt{
             }
         }
 
+        fn index_children(&mut self, node_id: NodeId) {
+            // Index the children of a node once it reaches a high fan-out, or after loading a model
+            if self.child_index.contains_key(&node_id) {
+                return;
+            }
+            if let Some(node) = self.nodes.get(node_id).filter(|node| node.children.len() >= CHILD_INDEX_MIN_FAN_OUT) {
+                let children_by_data = node.children.iter()
+                    .filter_map(|&child_id| self.nodes.get(child_id).and_then(|child| child.data).map(|data| (data, child_id)))
+                    .collect();
+                self.child_index.insert(node_id, children_by_data);
+            }
+        }
+
         pub fn add_sequence_to_root(&mut self, sequence: Vec<DataTypes>, sequence_attributes: Option<Vec<SequenceAttributes>>) {
             self.add_sequence(sequence, CPT::get_root_id(), sequence_attributes)
         }
@@ -284,6 +658,9 @@ pub mod cpt{
             sequence.iter().for_each(|&item|{
                 current_node_id = self.add_child(item, current_node_id);
             });
+            if let Some(items_inserted) = self.items_inserted.as_mut() {
+                *items_inserted += sequence.len();
+            }
 
             // If attributes have been provided, insert them in the attributes table
             if let Some(sequence_attributes_vec) = sequence_attributes {
@@ -295,26 +672,284 @@ pub mod cpt{
             }
         }
 
+        // Adds every sequence of another CPT to this one. The trees are merged from their roots:
+        // the nodes of `other` get new ids in this tree, and the attributes of sequences
+        // present in both trees are concatenated. This differs from adding the sequences of
+        // `other` with add_sequence, where a sequence added without attributes clears them:
+        // a tree does not record which of its sequences were added without attributes.
+        // `other` is checked like in validate, and left untouched if it is inconsistent.
+        pub fn merge(&mut self, other: &CPT<DataTypes>) -> Result<(), CptError> {
+            other.check_tree()?;
+            // Walk the other tree breadth first, so that each parent is mapped before its children
+            let mut id_map = vec![CPT::get_root_id(); other.nodes.len()];
+            let mut queue = std::collections::VecDeque::from(vec![CPT::get_root_id()]);
+            while let Some(other_id) = queue.pop_front() {
+                for &other_child_id in other.nodes[other_id].children.iter() {
+                    if let Some(child_data) = other.get_data(other_child_id) {
+                        id_map[other_child_id] = self.add_child(child_data, id_map[other_id]);
+                        queue.push_back(other_child_id);
+                    }
+                }
+            }
+            other.sequences_lookup_table.iter().for_each(|(&other_last_node_id, sequence_attributes)| {
+                self.sequences_lookup_table.entry(id_map[other_last_node_id]).or_default().extend(sequence_attributes.iter().cloned());
+            });
+            self.items_inserted = self.items_inserted.zip(other.items_inserted).map(|(items, other_items)| items + other_items);
+            Ok(())
+        }
+
+        // Trains `shards` CPTs in parallel threads, each on a contiguous part of the sequences,
+        // then merges them pairwise, also in parallel, into a single CPT.
+        pub fn train_parallel(sequences: Vec<TrainingSequence>, shards: usize) -> Result<CPT<DataTypes>, CptError> {
+            let shard_size = sequences.len().div_ceil(shards.max(1)).max(1);
+            let mut shard_sequences = Vec::<Vec<TrainingSequence>>::new();
+            let mut sequences_iter = sequences.into_iter().peekable();
+            while sequences_iter.peek().is_some() {
+                shard_sequences.push(sequences_iter.by_ref().take(shard_size).collect());
+            }
+
+            let mut cpts: Vec<CPT<DataTypes>> = std::thread::scope(|scope| {
+                let workers: Vec<_> = shard_sequences.into_iter().map(|shard| scope.spawn(move || {
+                    let mut cpt = CPT::new();
+                    shard.into_iter().for_each(|(sequence, sequence_attributes)| cpt.add_sequence_to_root(sequence, sequence_attributes));
+                    cpt
+                })).collect();
+                workers.into_iter().map(|worker| worker.join().expect("Training worker panicked")).collect()
+            });
+
+            while cpts.len() > 1 {
+                let mut pairs = Vec::<(CPT<DataTypes>, Option<CPT<DataTypes>>)>::new();
+                let mut cpts_iter = cpts.into_iter();
+                while let Some(left) = cpts_iter.next() {
+                    pairs.push((left, cpts_iter.next()));
+                }
+                cpts = std::thread::scope(|scope| {
+                    let workers: Vec<_> = pairs.into_iter().map(|(mut left, right)| scope.spawn(move || {
+                        if let Some(right) = right {
+                            left.merge(&right)?;
+                        }
+                        Ok(left)
+                    })).collect();
+                    workers.into_iter().map(|worker| worker.join().expect("Merge worker panicked")).collect::<Result<Vec<CPT<DataTypes>>, CptError>>()
+                })?;
+            }
+            Ok(cpts.pop().unwrap_or_default())
+        }
+
         pub fn match_sequence(&self, sequence: &[DataTypes], backwards: bool, match_functions: &[SequenceMatchFunction]) -> Vec<SequenceMatchResult> {
+            self.match_sequence_with_options(sequence, backwards, match_functions, &MatchOptions::default()).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        // Like `match_sequence`, with control over the candidate nodes and the number of results.
+        pub fn match_sequence_with_options(&self, sequence: &[DataTypes], backwards: bool, match_functions: &[SequenceMatchFunction], options: &MatchOptions) -> Result<Vec<SequenceMatchResult>, CptError> {
+            TreeView::match_sequence(self, sequence, backwards, match_functions, options)
+        }
+
+        // Like `match_sequence_with_options`, with user defined metrics. The scores of each
+        // matched node, and the aggregated scores, are in the order of the metrics.
+        pub fn match_sequence_with_metrics(&self, sequence: &[DataTypes], backwards: bool, metrics: &[&dyn SimilarityMetric], options: &MatchOptions) -> Result<Vec<MetricMatchResult>, CptError> {
+            TreeView::match_sequence_with_metrics(self, sequence, backwards, metrics, options)
+        }
+
+        pub fn match_sequence_backward(&self, sequence: &[DataTypes], match_functions: &[SequenceMatchFunction]) -> Vec<Vec<NodeMatchResult>>{
+            TreeView::match_sequence_backward(self, sequence, match_functions, &MatchOptions::default()).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        pub fn match_sequence_forward(&self, sequence: &[DataTypes], match_functions: &[SequenceMatchFunction]) -> Vec<Vec<NodeMatchResult>>{
+            TreeView::match_sequence_forward(self, sequence, match_functions, &MatchOptions::default()).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        pub fn predict(&self, sequence: &[DataTypes], prefix_length: usize) -> Vec<PredictedItem>{
+            self.try_predict(sequence, prefix_length).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        pub fn try_predict(&self, sequence: &[DataTypes], prefix_length: usize) -> Result<Vec<PredictedItem>, CptError>{
+            TreeView::try_predict(self, sequence, prefix_length)
+        }
+
+        pub(crate) fn prefix_values(sequence: &[DataTypes], prefix_length: usize) -> Result<Vec<DataTypes>, CptError> {
+            // The unique values among the last prefix_length items of the sequence
+            if prefix_length > sequence.len() {
+                return Err(CptError::InvalidPrefix { prefix_length, sequence_length: sequence.len() });
+            }
+            let mut prefix_set = sequence[(sequence.len() - prefix_length)..sequence.len()].to_vec();
+            prefix_set.sort();
+            prefix_set.dedup();
+            Ok(prefix_set)
+        }
+        // Returns the training sequences, identified by their last node, containing the given value.
+        pub fn prefix_value_sequence_ids(&self, prefix_value: DataTypes) -> Result<Vec<NodeId>, CptError> {
+            TreeView::prefix_value_sequence_ids(self, prefix_value)
+        }
+
+        // Predicts the next items of many sequences, spread over `threads` worker threads
+        // (0 uses every available core). The training sequences containing each prefix value
+        // are looked up once for the whole batch. Results are returned in input order.
+        pub fn predict_batch(&self, sequences: &[Vec<DataTypes>], prefix_length: usize, threads: usize) -> Vec<Result<Vec<PredictedItem>, CptError>> {
+            let threads = if threads == 0 {
+                std::thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1)
+            } else { threads };
+
+            // Resolve the unique prefix values of the whole batch against the inverted index
+            let mut batch_prefix_values: Vec<DataTypes> = sequences.iter()
+                .filter_map(|sequence| CPT::prefix_values(sequence, prefix_length).ok())
+                .flatten()
+                .collect();
+            batch_prefix_values.sort();
+            batch_prefix_values.dedup();
+            let prefix_sequence_ids: HashMap<DataTypes, Vec<NodeId>> = std::thread::scope(|scope| {
+                let workers: Vec<_> = batch_prefix_values.chunks(batch_prefix_values.len().div_ceil(threads).max(1))
+                    .map(|prefix_values| scope.spawn(move || {
+                        prefix_values.iter().map(|&prefix_value| {
+                            self.prefix_value_sequence_ids(prefix_value).map(|sequence_ids| (prefix_value, sequence_ids))
+                        }).collect::<Vec<Result<(DataTypes, Vec<NodeId>), CptError>>>()
+                    }))
+                    .collect();
+                workers.into_iter()
+                    .flat_map(|worker| worker.join().expect("Prediction worker panicked"))
+                    .filter_map(|resolved| resolved.ok())
+                    .collect()
+            });
+
+            // Then predict each query, every thread handling a contiguous chunk of the batch
+            std::thread::scope(|scope| {
+                let prefix_sequence_ids = &prefix_sequence_ids;
+                let workers: Vec<_> = sequences.chunks(sequences.len().div_ceil(threads).max(1))
+                    .map(|chunk| scope.spawn(move || {
+                        chunk.iter().map(|sequence| {
+                            let prefix_set = CPT::prefix_values(sequence, prefix_length)?;
+                            self.diagnostics.emit(DiagnosticEvent::PredictionPrefix { sequence, prefix_length, prefix_values: &prefix_set });
+                            if let Some(&missing_value) = prefix_set.iter().find(|prefix_value| !prefix_sequence_ids.contains_key(prefix_value)) {
+                                // The lookup failed while resolving the batch, report its error for this query
+                                self.prefix_value_sequence_ids(missing_value)?;
+                            }
+                            TreeView::predict_from_sequence_ids(self, sequence, &prefix_set, prefix_sequence_ids)
+                        }).collect::<Vec<Result<Vec<PredictedItem>, CptError>>>()
+                    }))
+                    .collect();
+                workers.into_iter()
+                    .flat_map(|worker| worker.join().expect("Prediction worker panicked"))
+                    .collect()
+            })
+        }
+    }
+
+    impl TreeView for CPT<DataTypes> {
+        type Index = InvertedIndex<DataTypes>;
+
+        fn index(&self) -> &InvertedIndex<DataTypes> {
+            &self.inverted_index
+        }
+
+        fn node_data(&self, id: NodeId) -> Option<DataTypes> {
+            self.nodes.get(id).and_then(|node| node.data)
+        }
+
+        fn node_parent(&self, id: NodeId) -> Option<NodeId> {
+            self.nodes.get(id).and_then(|node| node.parent)
+        }
+
+        fn node_children(&self, id: NodeId) -> Option<impl Iterator<Item = NodeId> + '_> {
+            self.nodes.get(id).map(|node| node.children.iter().copied())
+        }
+
+        fn is_sequence_end(&self, id: NodeId) -> bool {
+            self.sequences_lookup_table.contains_key(&id)
+        }
+
+        fn diagnostics(&self) -> &Diagnostics {
+            &self.diagnostics
+        }
+    }
+
+    fn item_similarities(match_functions: &[SequenceMatchFunction], node_data: DataTypes, item: DataTypes) -> Result<Vec<(SequenceMatchFunction, SimilarityScores)>, CptError> {
+        // Scores of a node matched with an item, sequence metrics are computed once the whole sequence is matched
+        match_functions.iter().filter(|function| !function.is_sequence_metric()).map(|&match_function| {
+            Ok((match_function, InvertedIndex::try_element_matching(match_function, node_data, item)?))
+        }).collect()
+    }
+
+    fn gapped_item_similarities(match_functions: &[SequenceMatchFunction], node_data: DataTypes, item: DataTypes, skipped: usize, options: &MatchOptions) -> Result<Vec<(SequenceMatchFunction, SimilarityScores)>, CptError> {
+        // Scores of a node matched with an item after skipping some nodes
+        let mut similarities = item_similarities(match_functions, node_data, item)?;
+        if skipped > 0 {
+            for similarity in similarities.iter_mut() {
+                similarity.1 = similarity.1.penalize(options.skip_penalty * skipped as f32)?;
+            }
+        }
+        Ok(similarities)
+    }
+
+
+    // A step of a forward match, shared by the partial matches extending it
+    struct PathStep<S> {
+        node_id: NodeId,
+        scores: S,
+        previous: Option<usize>,
+    }
+
+    // Applies the score threshold and the beam width of the options to the partial forward matches.
+    // `passes(total, threshold)` tells whether a total passes the threshold, `compare` is Greater for the better total.
+    fn prune_paths<T>(paths: &mut Vec<(usize, T)>, options: &MatchOptions, passes: impl Fn(&T, f32) -> bool, compare: impl Fn(&T, &T) -> Ordering) {
+        if let Some(threshold) = options.score_threshold {
+            paths.retain(|path| passes(&path.1, threshold));
+        }
+        if let Some(beam_width) = options.beam_width {
+            if paths.len() > beam_width {
+                // Ties keep the earliest partial matches, for a deterministic result
+                paths.sort_by(|a, b| compare(&b.1, &a.1).then_with(|| a.0.cmp(&b.0)));
+                paths.truncate(beam_width);
+            }
+        }
+    }
+
+    // Read access to a trained tree. The matching and prediction algorithms are written
+    // against this trait, so that `CPT` and `FrozenCPT` share them.
+    pub(crate) trait TreeView {
+        type Index: ValueIndex;
+
+        fn index(&self) -> &Self::Index;
+        // The data of a node, None for the root and unknown nodes
+        fn node_data(&self, id: NodeId) -> Option<DataTypes>;
+        fn node_parent(&self, id: NodeId) -> Option<NodeId>;
+        // The children of a node, None for unknown nodes
+        fn node_children(&self, id: NodeId) -> Option<impl Iterator<Item = NodeId> + '_>;
+        // Whether a training sequence ends at this node
+        fn is_sequence_end(&self, id: NodeId) -> bool;
+        // Receives the intermediate state of match_sequence and predict, does nothing by default
+        fn diagnostics(&self) -> &Diagnostics;
+
+        fn match_sequence(&self, sequence: &[DataTypes], backwards: bool, match_functions: &[SequenceMatchFunction], options: &MatchOptions) -> Result<Vec<SequenceMatchResult>, CptError> {
             // Given an input sequence, match the longest possible sequences in the CPT.
             // This is can be implemented in two ways:
             //  - starting from the first item of the sequence,
             //    and search forward in the tree,
             //  - start from the last item of the sequence and
             //    search backwards in the tree
-            let mut matched_sequences: Vec<Vec<NodeMatchResult>> = Vec::<Vec::<NodeMatchResult>>::new();
-            if backwards{
-                matched_sequences = self.match_sequence_backward(sequence, match_functions);
+            let matched_sequences: Vec<Vec<NodeMatchResult>> = if backwards{
+                self.match_sequence_backward(sequence, match_functions, options)?
             }else{
-                matched_sequences = self.match_sequence_forward(sequence, match_functions);
-            }
+                self.match_sequence_forward(sequence, match_functions, options)?
+            };
 
-            let mut matched_sequences_agg = Vec::<(Vec<NodeMatchResult>, Scores)>::new();
-            matched_sequences.into_iter().for_each(|similar_sequence| {
+            let mut matched_sequences_agg = Vec::<SequenceMatchResult>::new();
+            for similar_sequence in matched_sequences.into_iter() {
                 let mut sum_scores = Scores::new() ;
-                similar_sequence.iter().for_each(|node_id_scores|{
-                    node_id_scores.1.iter().for_each(|node_id_score| *sum_scores.entry(node_id_score.0).or_insert(node_id_score.1.get_zero()) += node_id_score.1  )
-                });
+                for node_id_score in similar_sequence.iter().flat_map(|node_id_scores| node_id_scores.1.iter()) {
+                    let sum_score = match sum_scores.get(&node_id_score.0) {
+                        Some(sum_score) => *sum_score,
+                        None => node_id_score.1.try_get_zero()?
+                    };
+                    sum_scores.insert(node_id_score.0, sum_score.try_add(node_id_score.1)?);
+                }
+                // Sequence metrics compare the query with the training sequence ending at the last matched node,
+                // skipped nodes included
+                if let Some(last_node_id) = similar_sequence.last().map(|node_id_scores| node_id_scores.0).filter(|_| match_functions.iter().any(|function| function.is_sequence_metric())) {
+                    let window = self.values_up_to(last_node_id, sequence.len() + options.alignment_margin);
+                    for &metric in match_functions.iter().filter(|function| function.is_sequence_metric()) {
+                        sum_scores.insert(metric, sequence_similarity(metric, sequence, &window)?);
+                    }
+                }
                 matched_sequences_agg.push(
                         (similar_sequence, sum_scores)
                 );
@@ -324,107 +959,255 @@ pub mod cpt{
                 //         Scores::SequenceLength(seq_len), Scores::SimilarityScores(sum_score)
                 //     ])
                 // );
-            });
-            // Get all metrics types, and sort the list using each of them
-            match_functions.iter().for_each(|metric| {
-                matched_sequences_agg.sort_by(|a, b| {
-                    a.1.get(metric).expect(&format!("Metric {:?} not found in element {:?}", metric, a.1))
-                        .cmp(b.1.get(metric).expect(&format!("Metric {:?} not found in element {:?}", metric, b.1)))
-                })
-            });
-            // matched_sequences_agg.sort_by(|a, b| a.1.get(0).unwrap().cmp(b.1.get(0).unwrap()) );
+            }
+            // Rank the list using the scores of each metric, the best matches are sorted last
+            let score = |scores: &Scores, metric: usize| -> Result<SimilarityScores, CptError> {
+                scores.get(&match_functions[metric]).copied()
+                    .ok_or_else(|| CptError::IncompatibleTypes(format!("Metric {:?} not found in element {:?}", match_functions[metric], scores)))
+            };
+            let mut matched_sequences_agg = rank(matched_sequences_agg, &options.ranking, match_functions.len(),
+                |a, b, metric| score(a, metric)?.try_cmp(&score(b, metric)?),
+                |scores, metric| Ok(score(scores, metric)?.utility()))?;
+
+            if let Some(max_results) = options.max_results {
+                matched_sequences_agg.drain(..matched_sequences_agg.len().saturating_sub(max_results));
+            }
 
-            println!("Matched sequences and scores:" );
-            matched_sequences_agg.iter().for_each(|seq_score| println!("{:?}", seq_score) );
-            matched_sequences_agg
+            self.diagnostics().emit(DiagnosticEvent::SequenceMatches { results: &matched_sequences_agg });
+            Ok(matched_sequences_agg)
         }
 
-        pub fn match_sequence_backward(&self, sequence: &[DataTypes], match_functions: &[SequenceMatchFunction]) -> Vec<Vec<NodeMatchResult>>{
+        fn match_sequence_backward(&self, sequence: &[DataTypes], match_functions: &[SequenceMatchFunction], options: &MatchOptions) -> Result<Vec<Vec<NodeMatchResult>>, CptError>{
             // This returns lists of NodeIDs for matched sequences
-            let mut current_node_ids = Vec::<Vec<NodeMatchResult> >::new();
-            println!("Matching sequence backwards {:?}", sequence);
+            self.diagnostics().emit(DiagnosticEvent::MatchSequence { sequence, backwards: true });
 
             // Given an input sequence, get its last item
             // Then, check the parents with the previous value
-            let mut new_sequence = sequence.to_vec();
-            new_sequence.reverse();
-
-            let mut sequence_iter = new_sequence.iter();
-
-            let mut count = 1;
-
-            // Get the last element of the sequence
-            if let Some(last_value) = sequence_iter.next() {
-                // Get all nodes matching this value, this is our initial list of possible nodes
-                    let possible_node_ids = self.inverted_index.get_similar_value_ids(match_functions, *last_value);
-                    current_node_ids = possible_node_ids.into_iter().map(|(node_id, scores)| vec![(node_id, scores)] ).collect();
-                    // Get the previous item in the sequence to match,
-                    // at each iteration we will filter the possible_node_ids
-                    while let Some(&next_item) = sequence_iter.next(){
-                        // println!("Current NodeIds at item {:?}th item in sequence {:?}: {:?}", count, sequence, current_node_ids);
-                        count = count + 1;
-                        current_node_ids = current_node_ids.into_iter().map(|possible_node_ids|
-                            if let Some(possible_node) = self.get(possible_node_ids.first().unwrap().0){
-                                if let Some(parent_node_id) = possible_node.parent{
-                                    if let Some(parent_node_data) = self.get_data(parent_node_id){
-                                        let similarities = match_functions.iter().map(|&match_function| {
-                                            (match_function, InvertedIndex::element_matching(match_function, parent_node_data, next_item))
-                                        }).collect();
-                                        [vec![(parent_node_id, similarities)], possible_node_ids].concat()
-                                    } else { possible_node_ids }
-                                } else { possible_node_ids }
-                            } else { possible_node_ids }
-                        ).collect();
+            match sequence.last() {
+                Some(last_value) => {
+                    // Get all nodes matching this value, this is our initial list of possible nodes
+                    let possible_node_ids = self.index().retrieved_value_ids(match_functions, *last_value, options.retrieve_function)?;
+                    let score_error = RefCell::new(None);
+                    let matched_sequences = self.walk_backward(sequence, possible_node_ids, options.max_gap,
+                        |node_data, item, skipped| keep_first_error(&score_error, gapped_item_similarities(match_functions, node_data, item, skipped, options), Vec::new));
+                    match score_error.into_inner() {
+                        Some(e) => Err(e),
+                        None => Ok(matched_sequences)
                     }
-                
+                },
+                None => Ok(vec![])
             }
-            // println!("Matching node_ids at the end for sequence {:?}: {:?}", sequence, current_node_ids);
-            current_node_ids
         }
 
-        pub fn match_sequence_forward(&self, sequence: &[DataTypes], match_functions: &[SequenceMatchFunction]) -> Vec<Vec<NodeMatchResult>>{
-            
+        fn match_sequence_forward(&self, sequence: &[DataTypes], match_functions: &[SequenceMatchFunction], options: &MatchOptions) -> Result<Vec<Vec<NodeMatchResult>>, CptError>{
             // This returns the last NodeID of the longest matched sequence
-            let mut current_node_ids = Vec::<Vec<NodeMatchResult>>::new();
-            println!("Matching sequence forward {:?}", sequence);
+            self.diagnostics().emit(DiagnosticEvent::MatchSequence { sequence, backwards: false });
 
             // Given an input sequence, get its first item
             // Then, check the children with the next value
-            let mut sequence_iter = sequence.iter();
-
-            let mut count = 1;
-
-            // Get the last element of the sequence
-            if let Some(first_value) = sequence_iter.next() {
-                // Get all nodes matching this value, this is our initial list of possible nodes
-                let possible_node_ids = self.inverted_index.get_similar_value_ids(match_functions, *first_value);
-                    current_node_ids = possible_node_ids.into_iter().map(|(node_id, scores)| vec![(node_id, scores)]).collect();
-
-                    // Get the next item in the sequence to match,
-                    // at each iteration we will filter the possible_node_ids
-                    while let Some(&next_item) = sequence_iter.next(){
-                        // println!("Current NodeIds at item {:?}th item in sequence {:?}: {:?}", count, sequence, current_node_ids);
-                        count = count + 1;
-                        current_node_ids = current_node_ids.into_iter().filter_map(|possible_node_ids|
-                                if let Some(possible_node) = self.get(possible_node_ids.last().unwrap().0){
-                                    Some(possible_node.children.clone().into_iter().filter_map( |child_node_id| {
-                                        if let Some(child_node_data) = self.get_data(child_node_id){
-                                            Some([possible_node_ids.clone(), vec![
-                                                (child_node_id, match_functions.iter().map(|&match_function| {
-                                                    (match_function, InvertedIndex::element_matching(match_function, child_node_data, next_item))
-                                                }).collect())
-                                            ]].concat())
-                                        } else { None }
-                                    }).collect::<Vec<Vec<NodeMatchResult>>>())
-                                } else { None }
-                        ).collect::<Vec<Vec<Vec<NodeMatchResult>>>>().concat();
+            match sequence.first() {
+                Some(first_value) => {
+                    // Get all nodes matching this value, this is our initial list of possible nodes
+                    let possible_node_ids = self.index().retrieved_value_ids(match_functions, *first_value, options.retrieve_function)?;
+                    // The partial matches are pruned on their total scores, in the order of the match functions
+                    let score_error = RefCell::new(None);
+                    let matched_sequences = self.walk_forward(sequence, possible_node_ids, options.max_gap,
+                        |node_data, item, skipped| keep_first_error(&score_error, gapped_item_similarities(match_functions, node_data, item, skipped, options), Vec::new),
+                        |total: Option<&Vec<(SequenceMatchFunction, SimilarityScores)>>, scores| match total {
+                            Some(total) => total.iter().zip(scores.iter())
+                                .map(|(total, score)| (total.0, keep_first_error(&score_error, total.1.try_add(score.1), || total.1)))
+                                .collect(),
+                            None => scores.clone()
+                        },
+                        |paths| prune_paths(paths, options,
+                            |total, threshold| total.first().is_none_or(|(function, score)| function.compare(score.value(), threshold) != Ordering::Less),
+                            |a, b| a.iter().zip(b.iter())
+                                .map(|(a, b)| keep_first_error(&score_error, a.1.try_cmp(&b.1), || Ordering::Equal))
+                                .find(|&ordering| ordering != Ordering::Equal).unwrap_or(Ordering::Equal))
+                    );
+                    match score_error.into_inner() {
+                        Some(e) => Err(e),
+                        None => Ok(matched_sequences)
                     }
+                },
+                None => Ok(vec![])
+            }
+        }
+
+        fn match_sequence_with_metrics(&self, sequence: &[DataTypes], backwards: bool, metrics: &[&dyn SimilarityMetric], options: &MatchOptions) -> Result<Vec<MetricMatchResult>, CptError> {
+            self.diagnostics().emit(DiagnosticEvent::MatchSequence { sequence, backwards });
+            let start_value = if backwards { sequence.last() } else { sequence.first() };
+            let start_node_ids = match start_value {
+                Some(&start_value) => self.index().metric_value_ids(metrics, start_value, options.retrieve_function)?,
+                None => return Ok(vec![])
+            };
+            // The walk cannot fail, the first scoring error is kept and returned after it
+            let score_error = RefCell::new(None);
+            let score = |node_data: DataTypes, item: DataTypes, skipped: usize| -> Vec<f32> {
+                let penalty = options.skip_penalty * skipped as f32;
+                metrics.iter().map(|metric| match keep_first_error(&score_error, metric.score(node_data, item), || metric.zero()) {
+                    score if metric.kind() == ScoreKind::Cost => score + penalty,
+                    score => score - penalty
+                }).collect()
+            };
+            let matched_sequences = if backwards {
+                self.walk_backward(sequence, start_node_ids, options.max_gap, score)
+            } else {
+                self.walk_forward(sequence, start_node_ids, options.max_gap, score,
+                    |total: Option<&Vec<f32>>, scores| metrics.iter().enumerate()
+                        .map(|(index, metric)| metric.aggregate(total.map_or(metric.zero(), |total| total[index]), scores[index]))
+                        .collect(),
+                    |paths| prune_paths(paths, options,
+                        |total, threshold| metrics.first().is_none_or(|metric| metric.compare(total[0], threshold) != Ordering::Less),
+                        |a, b| metrics.iter().enumerate().map(|(index, metric)| metric.compare(a[index], b[index])).find(|&ordering| ordering != Ordering::Equal).unwrap_or(Ordering::Equal)))
+            };
+            if let Some(e) = score_error.into_inner() {
+                return Err(e);
+            }
+
+            let matched_sequences_agg: Vec<MetricMatchResult> = matched_sequences.into_iter().map(|similar_sequence| {
+                let aggregated_scores = metrics.iter().enumerate().map(|(index, metric)| {
+                    similar_sequence.iter().fold(metric.zero(), |total, node_scores| metric.aggregate(total, node_scores.1[index]))
+                }).collect();
+                (similar_sequence, aggregated_scores)
+            }).collect();
+            // Like match_sequence, the best matches are sorted last
+            let mut matched_sequences_agg = rank(matched_sequences_agg, &options.ranking, metrics.len(),
+                |a, b, metric| Ok(metrics[metric].compare(a[metric], b[metric])),
+                |scores, metric| Ok(match metrics[metric].kind() {
+                    ScoreKind::Cost => -scores[metric],
+                    ScoreKind::Reward => scores[metric],
+                }))?;
+            if let Some(max_results) = options.max_results {
+                matched_sequences_agg.drain(..matched_sequences_agg.len().saturating_sub(max_results));
+            }
+            Ok(matched_sequences_agg)
+        }
+
+        fn walk_backward<S: Clone>(&self, sequence: &[DataTypes], start_node_ids: HashMap<NodeId, S>, max_gap: usize, score: impl Fn(DataTypes, DataTypes, usize) -> S) -> Vec<Vec<(NodeId, S)>> {
+            // The start nodes are matched with the last item of the sequence
+            let mut current_node_ids: Vec<Vec<(NodeId, S)>> = start_node_ids.into_iter().map(|(node_id, scores)| vec![(node_id, scores)]).collect();
+            // Get the previous item in the sequence to match,
+            // at each iteration we will extend the possible_node_ids with their parent,
+            // or with each of their ancestors up to max_gap nodes above it
+            for &next_item in sequence.iter().rev().skip(1) {
+                current_node_ids = current_node_ids.into_iter().flat_map(|possible_node_ids| {
+                    let ancestors = self.gapped_ancestors(possible_node_ids[0].0, max_gap);
+                    if ancestors.is_empty() {
+                        return vec![possible_node_ids];
+                    }
+                    ancestors.into_iter().map(|(ancestor_node_id, ancestor_node_data, skipped)| {
+                        let mut extended_node_ids = Vec::with_capacity(possible_node_ids.len() + 1);
+                        extended_node_ids.push((ancestor_node_id, score(ancestor_node_data, next_item, skipped)));
+                        extended_node_ids.extend(possible_node_ids.iter().cloned());
+                        extended_node_ids
+                    }).collect()
+                }).collect();
             }
-            // println!("Matching node_ids at the end for sequence {:?}: {:?}", sequence, current_node_ids);
             current_node_ids
         }
 
-        pub fn predict(&self, sequence: &[DataTypes], prefix_length: usize) -> Vec<(DataTypes, usize, f32)>{
+        // The values of at most `length` nodes ending at a node, read through the parent links, in sequence order
+        fn values_up_to(&self, id: NodeId, length: usize) -> Vec<DataTypes> {
+            let mut values = Vec::with_capacity(length);
+            let mut current_node_id = Some(id);
+            while let Some((node_id, node_data)) = current_node_id.filter(|_| values.len() < length).and_then(|id| self.node_data(id).map(|data| (id, data))) {
+                values.push(node_data);
+                current_node_id = self.node_parent(node_id);
+            }
+            values.reverse();
+            values
+        }
+
+        // The ancestors of a node with data, from its parent up to max_gap nodes above it, with the number of nodes skipped
+        fn gapped_ancestors(&self, id: NodeId, max_gap: usize) -> Vec<(NodeId, DataTypes, usize)> {
+            let mut ancestors = Vec::new();
+            let mut current_node_id = id;
+            for skipped in 0..=max_gap {
+                match self.node_parent(current_node_id).and_then(|parent_node_id| self.node_data(parent_node_id).map(|data| (parent_node_id, data))) {
+                    Some((parent_node_id, parent_node_data)) => {
+                        ancestors.push((parent_node_id, parent_node_data, skipped));
+                        current_node_id = parent_node_id;
+                    },
+                    None => break
+                }
+            }
+            ancestors
+        }
+
+        // The descendants of a node, from its children down to max_gap nodes below them, with the number of nodes skipped
+        fn gapped_descendants(&self, id: NodeId, max_gap: usize) -> Vec<(NodeId, DataTypes, usize)> {
+            let mut descendants = Vec::new();
+            let mut level = vec![id];
+            for skipped in 0..=max_gap {
+                let mut next_level = Vec::new();
+                for node_id in level {
+                    if let Some(children) = self.node_children(node_id) {
+                        for child_node_id in children {
+                            if let Some(child_node_data) = self.node_data(child_node_id) {
+                                descendants.push((child_node_id, child_node_data, skipped));
+                                next_level.push(child_node_id);
+                            }
+                        }
+                    }
+                }
+                // The leaves are reached, whatever the gap left
+                if next_level.is_empty() {
+                    break;
+                }
+                level = next_level;
+            }
+            descendants
+        }
+
+        fn walk_forward<S: Clone, T>(&self, sequence: &[DataTypes], start_node_ids: HashMap<NodeId, S>, max_gap: usize, score: impl Fn(DataTypes, DataTypes, usize) -> S,
+                accumulate: impl Fn(Option<&T>, &S) -> T, prune: impl Fn(&mut Vec<(usize, T)>)) -> Vec<Vec<(NodeId, S)>> {
+            // The partial matches share their steps, each step pointing to the previous one,
+            // and the matched node ids are only collected for the matches left at the end.
+            // A partial match is the index of its last step, with the total of its scores.
+            let mut steps = Vec::<PathStep<S>>::new();
+            let mut start_node_ids: Vec<(NodeId, S)> = start_node_ids.into_iter().collect();
+            start_node_ids.sort_by_key(|start| start.0);
+            // The start nodes are matched with the first item of the sequence
+            let mut paths: Vec<(usize, T)> = start_node_ids.into_iter().map(|(node_id, scores)| {
+                let total = accumulate(None, &scores);
+                steps.push(PathStep { node_id, scores, previous: None });
+                (steps.len() - 1, total)
+            }).collect();
+            prune(&mut paths);
+            // Get the next item in the sequence to match,
+            // at each iteration the partial matches are extended with the children of their last node,
+            // or with each of their descendants up to max_gap nodes below them
+            for &next_item in sequence.iter().skip(1) {
+                let mut next_paths = Vec::<(usize, T)>::new();
+                for (step, total) in paths.iter() {
+                    for (child_node_id, child_node_data, skipped) in self.gapped_descendants(steps[*step].node_id, max_gap) {
+                        let scores = score(child_node_data, next_item, skipped);
+                        next_paths.push((steps.len(), accumulate(Some(total), &scores)));
+                        steps.push(PathStep { node_id: child_node_id, scores, previous: Some(*step) });
+                    }
+                }
+                paths = next_paths;
+                prune(&mut paths);
+            }
+            paths.into_iter().map(|(last_step, _total)| {
+                let mut path = Vec::<(NodeId, S)>::new();
+                let mut current_step = Some(last_step);
+                while let Some(step) = current_step {
+                    path.push((steps[step].node_id, steps[step].scores.clone()));
+                    current_step = steps[step].previous;
+                }
+                path.reverse();
+                path
+            }).collect()
+        }
+
+        fn try_predict(&self, sequence: &[DataTypes], prefix_length: usize) -> Result<Vec<PredictedItem>, CptError>{
+            Ok(self.try_predict_with_consequents(sequence, prefix_length)?.0)
+        }
+
+        // Same as try_predict, also returning the consequents the predictions were counted on
+        fn try_predict_with_consequents(&self, sequence: &[DataTypes], prefix_length: usize) -> Result<(Vec<PredictedItem>, Vec<Consequent>), CptError>{
             // This is an implementation of the prediction algorithm implemented in
             // ADMA2013_Compact_Prediction_tree
             // The goal is to predict the next values of an input sequence.
@@ -436,39 +1219,56 @@ pub mod cpt{
             // The output is a sorted list of potential next items, with their two prediction score:
             // E.g [(Integer(1), 3, 3.0), ...]
             // The first step is to identify the unique value in our prefix,
-            let prefix = &sequence[(sequence.len() - prefix_length)..sequence.len()].to_vec();
-            let mut prefix_set = prefix.clone();
-            prefix_set.sort();
-            prefix_set.dedup();
-            println!("Looking for sequences in the training set with the last {:?} values in {:?}", prefix_length, sequence);
-            println!("Prediction prefix unique values: {:?}", prefix_set);
+            let prefix_set = CPT::prefix_values(sequence, prefix_length)?;
+            self.diagnostics().emit(DiagnosticEvent::PredictionPrefix { sequence, prefix_length, prefix_values: &prefix_set });
 
             // For each of the unique item in the prefix, get the ids of the sequence that contain them
-            let all_match_sequence_ids: Vec<Vec<NodeId>> = prefix_set.iter().map(|prefix_value| {
-                // We get the nodes that match these values
-                if let Some(node_ids) = self.inverted_index.get_value_ids(*prefix_value){
-                    // For each node matching a given value, we have to find
-                    // What training sequences match these nodes
-                    // We will descend the nodes children until we reach an end
-                    let mut current_node_ids: Vec<NodeId> = node_ids.clone();
-                    let mut leaf_node_ids: Vec<NodeId> =  Vec::<NodeId>::new();
-                    println!("Prefix unique value {:?} matched with {:?} nodes", prefix_value, node_ids.len());
-                    while current_node_ids.len() > 0{
-                        leaf_node_ids.extend(current_node_ids.iter().filter(|&node_id| self.get(*node_id).unwrap().children.len() == 0));
-                        current_node_ids = current_node_ids.clone().into_iter()
-                                .filter(|&node_id| self.get(node_id).unwrap().children.len() > 0)
-                                .map(|node_id| self.get(node_id).unwrap().children.clone() ).collect::<Vec<Vec<NodeId>>>().concat();
+            let prefix_sequence_ids = prefix_set.iter().map(|&prefix_value| {
+                Ok((prefix_value, self.prefix_value_sequence_ids(prefix_value)?))
+            }).collect::<Result<HashMap<DataTypes, Vec<NodeId>>, CptError>>()?;
+
+            self.predict_with_consequents(sequence, &prefix_set, &prefix_sequence_ids)
+        }
+
+        fn prefix_value_sequence_ids(&self, prefix_value: DataTypes) -> Result<Vec<NodeId>, CptError> {
+            // We get the nodes that match these values
+            if let Some(node_ids) = self.index().value_node_ids(prefix_value){
+                // For each node matching a given value, we have to find
+                // What training sequences match these nodes
+                // We will descend the nodes children until we reach an end
+                let node_count = node_ids.len();
+                let mut current_node_ids: Vec<NodeId> = node_ids.collect();
+                let mut leaf_node_ids: Vec<NodeId> =  Vec::<NodeId>::new();
+                while !current_node_ids.is_empty(){
+                    let mut children_ids = Vec::<NodeId>::new();
+                    for node_id in current_node_ids {
+                        let child_count = children_ids.len();
+                        children_ids.extend(self.node_children(node_id).ok_or(CptError::UnknownNode(node_id))?);
+                        if children_ids.len() == child_count {
+                            leaf_node_ids.push(node_id);
+                        }
                     }
-                    // Now let's lookup the training sequences that point to the leaf nodes
-                    let match_sequence_ids: Vec<NodeId> = leaf_node_ids.iter().filter_map(|leaf_node_id| {
-                        if let Some(_leaf_node) = self.sequences_lookup_table.get(leaf_node_id){
-                            Some(*leaf_node_id)
-                        } else { None }
-                    }).collect();
-                    println!("Matching sequences for prefix value {:?}: {:?}", prefix_value, match_sequence_ids);
-                    match_sequence_ids
-                } else { vec![] }
-            }).collect();
+                    current_node_ids = children_ids;
+                }
+                // Now let's lookup the training sequences that point to the leaf nodes
+                let match_sequence_ids: Vec<NodeId> = leaf_node_ids.into_iter()
+                    .filter(|&leaf_node_id| self.is_sequence_end(leaf_node_id))
+                    .collect();
+                self.diagnostics().emit(DiagnosticEvent::PrefixValueSequences {
+                    prefix_value, node_count, sequence_ids: &match_sequence_ids
+                });
+                Ok(match_sequence_ids)
+            } else { Ok(vec![]) }
+        }
+
+        fn predict_from_sequence_ids(&self, sequence: &[DataTypes], prefix_set: &[DataTypes], prefix_sequence_ids: &HashMap<DataTypes, Vec<NodeId>>) -> Result<Vec<PredictedItem>, CptError> {
+            Ok(self.predict_with_consequents(sequence, prefix_set, prefix_sequence_ids)?.0)
+        }
+
+        fn predict_with_consequents(&self, sequence: &[DataTypes], prefix_set: &[DataTypes], prefix_sequence_ids: &HashMap<DataTypes, Vec<NodeId>>) -> Result<(Vec<PredictedItem>, Vec<Consequent>), CptError> {
+            let all_match_sequence_ids: Vec<Vec<NodeId>> = prefix_set.iter()
+                .filter_map(|prefix_value| prefix_sequence_ids.get(prefix_value).cloned())
+                .collect();
 
             // Now we have the list of sequences matching each element in the prefix, let's look at the unique sequence ids
             let mut unique_matched_sequence_ids = all_match_sequence_ids.concat();
@@ -479,39 +1279,36 @@ pub mod cpt{
             // For each sequence, let's look at the last occurence of an item the sequence:
             // Given the input sequence xxyy with yy being the prefix, If the training Sequence aabbxxyz exists, the consequent returned is yz
             let mut count_consequent_values_support = HashMap::<DataTypes,usize>::new();
+            let mut consequents = Vec::<Consequent>::with_capacity(unique_matched_sequence_ids.len());
             unique_matched_sequence_ids.clone().into_iter().for_each(|last_node_id| {
                     let mut current_node_id = last_node_id;
                     // let mut consequent: Vec<&Node<DataTypes>> = Vec::<&Node<DataTypes>>::new();
                     let mut consequent: Vec<NodeId> = Vec::<NodeId>::new();
-                    while let Some(current_node) = self.get(current_node_id) {
-                        // consequent.push(current_node);
-                        if let Some(node_data) = current_node.data {
+                    while let Some(node_data) = self.node_data(current_node_id) {
+                        // Only the root and unknown nodes have no data, the walk stops there
+                        // Now check whether the current node data belongs to the input sequence
+                        if sequence.contains(&node_data) {
+                            break;
+                        }
+                        consequent.push(current_node_id);
 
-                            // Now check whether the current node data belongs to the input sequence
-                            if sequence.iter().any(|&sequence_value| node_data == sequence_value ) {
-                                break;
-                            }
-                            else{
-                                consequent.push(current_node_id);
-                                
-                                // This will count the amount of each value in the consequents
-                                *count_consequent_values_support.entry(node_data).or_insert(0) += 1;
-
-                                // If not, retry the current node's parent
-                                if let Some(current_node_parent) = current_node.parent{
-                                    current_node_id = current_node_parent;
-                                }
-                                else{
-                                    break;
-                                }
-                            }
+                        // This will count the amount of each value in the consequents
+                        *count_consequent_values_support.entry(node_data).or_insert(0) += 1;
+
+                        // If not, retry the current node's parent
+                        if let Some(current_node_parent) = self.node_parent(current_node_id){
+                            current_node_id = current_node_parent;
                         }
+                        else{
+                            break;
+                        }
+                    }
                     // Consequent items have been pushed, need to reverse the list
                     consequent.reverse();
                     // The consequent given a training sequence is now:
                     // xyyyyy: x being the item that the training sequence and the input sequence have in common
-                    println!("Consequent for input sequence seq{:?} given training sequence {:?}: {:?}", sequence, last_node_id, consequent.iter().map(|node_id| format!("{:?}, {:?}", node_id, self.get_data(*node_id))).collect::<Vec<String>>().join(" -> "));
-                }
+                    self.diagnostics().emit(DiagnosticEvent::Consequent { sequence, sequence_id: last_node_id, consequent: &consequent });
+                    consequents.push((last_node_id, consequent));
             });
 
             // The final step is to calculate the score of each consequent, using the following metrics:
@@ -519,7 +1316,7 @@ pub mod cpt{
             // The support is calculated for each individual value in our consequents.
             // It is the number of times a value appears in training sequences that matches our input sequence
             // In our case, it will be the unique count of values in the consequents, counted in the unique_matched_sequence_ids.
-            
+        
             // let mut count_matched_sequences_values_support = HashMap::<DataTypes,usize>::new();
             // unique_matched_sequence_ids.iter().for_each(|sequence_id| {
             //     if let Some(&last_node_id) = self.sequences_lookup_table.get(*sequence_id){
@@ -544,17 +1341,16 @@ pub mod cpt{
             //     }
             // });
             // We now have the count of consequent's unique values among matched training sequences
-            println!("Count of consequent's unique values among consequents: {:?}: {:?}", unique_matched_sequence_ids, count_consequent_values_support);
-        
-        
+            self.diagnostics().emit(DiagnosticEvent::ConsequentSupport { sequence_ids: &unique_matched_sequence_ids, support: &count_consequent_values_support });
+    
+    
             // The secondary metric is the confidence: for each item in the support counting hashmap,
             // we divide the support value by the number of time this item appears in the tree
-            let mut count_consequent_values_support_confidence:  Vec<(DataTypes, usize, f32)> = count_consequent_values_support.iter().map(|(item, support)|{
-                    (*item, *support, (*support as f32) / (
-                        self.inverted_index.get_value_ids(*item)
-                            .expect(&format!("Cannot find item {:?} in tree values {:?}", item, self.inverted_index.values)).len() as f32)
-                    )
-                }).collect();
+            let mut count_consequent_values_support_confidence:  Vec<PredictedItem> = count_consequent_values_support.iter().map(|(item, support)|{
+                    let item_node_ids = self.index().value_node_ids(*item)
+                        .ok_or_else(|| CptError::CorruptModel(format!("Cannot find item {:?} in tree values {:?}", item, self.index().values())))?;
+                    Ok((*item, *support, (*support as f32) / (item_node_ids.len() as f32)))
+                }).collect::<Result<Vec<PredictedItem>, CptError>>()?;
 
             // We now have the confidence value for each indivual item        
             // Finally we sort the values using the support and the confidence:
@@ -575,8 +1371,8 @@ pub mod cpt{
                     }
             });
             count_consequent_values_support_confidence.reverse();
-            println!("Sorted list of predicted items and their scores: {:?}", count_consequent_values_support_confidence);
-            count_consequent_values_support_confidence
+            self.diagnostics().emit(DiagnosticEvent::Predictions { predictions: &count_consequent_values_support_confidence });
+            Ok((count_consequent_values_support_confidence, consequents))
         }
     }
-}
\ No newline at end of file
+}
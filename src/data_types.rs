@@ -6,6 +6,7 @@ pub mod data_types {
     use serde::{Serialize, Deserialize};
 
     use crate::cpt::cpt::SequenceMatchFunction as SequenceMatchFunction;
+    use crate::errors::errors::CptError;
 
     #[derive(Serialize, Deserialize, Debug)]
     #[derive(Copy, Clone, Eq, PartialOrd, PartialEq, Ord, Hash)]
@@ -33,27 +34,28 @@ pub mod data_types {
 
     impl DataTypes{
         pub fn compute_similarity(self, match_function: SequenceMatchFunction, other: Self) -> SimilarityScores {
+            self.try_compute_similarity(match_function, other).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        pub fn try_compute_similarity(self, match_function: SequenceMatchFunction, other: Self) -> Result<SimilarityScores, CptError> {
             // This functions matches a sequence matching function and a
             match match_function {
-                SequenceMatchFunction::StrictEqual => SimilarityScores::IsEqual(true),
-                SequenceMatchFunction::SequenceLength => SimilarityScores::Length(1),
+                SequenceMatchFunction::StrictEqual => Ok(SimilarityScores::IsEqual(true)),
+                SequenceMatchFunction::SequenceLength => Ok(SimilarityScores::Length(1)),
                 SequenceMatchFunction::AlgebraicDistance => {
-                    match self {
-                        DataTypes::Integer(self_value) => {
-                            match other {
-                                DataTypes::Integer(other_value) => {
-                                    if self_value > other_value{
-                                        SimilarityScores::Distance((self_value - other_value) as f32)
-                                    }else{
-                                        SimilarityScores::Distance((other_value - self_value) as f32)
-                                    }
-                                }
-                                _ => panic!("Cannot compare {:?} with {:?}", self, other)
+                    match (self, other) {
+                        (DataTypes::Integer(self_value), DataTypes::Integer(other_value)) => {
+                            if self_value > other_value{
+                                Ok(SimilarityScores::Distance((self_value - other_value) as f32))
+                            }else{
+                                Ok(SimilarityScores::Distance((other_value - self_value) as f32))
                             }
                         },
-                        _ => panic!("Cannot compare {:?} with {:?}", self, other)
+                        _ => Err(CptError::IncompatibleTypes(format!("Cannot compare {:?} with {:?}", self, other)))
                     }
-                }
+                },
+                // Sequence metrics are computed by the alignment module
+                _ => Err(CptError::IncompatibleTypes(format!("{:?} compares sequences, not items", match_function)))
             }
         }
     }
@@ -61,61 +63,88 @@ pub mod data_types {
     impl Eq for SimilarityScores {}
 
     impl Ord for SimilarityScores {
-        // This function differentiates Cost metrics from Reward metrics:
-        // Example:
-        //      The Distance being a cost function, the ordering will be reversed (further means more cost)
-        //      The Similarity being a Reward function, the ordering will be preserved
         fn cmp(&self, other: &Self) -> Ordering {
-            match self {
-                SimilarityScores::Similarity(self_value) => {
-                    match other {
-                        SimilarityScores::Similarity(other_value) => {
-                            if self_value == other_value { Ordering::Equal }
-                            else{
-                                if self_value > other_value{ Ordering::Greater }
-                                else{ Ordering::Less }
-                            }
-                        }
-                        _ => panic!("Cannot compare {:?} with {:?}", self, other)
-                    }
+            self.try_cmp(other).unwrap_or_else(|e| panic!("{}", e))
+        }
+    }
+
+    impl SimilarityScores{
+        pub fn try_cmp(&self, other: &Self) -> Result<Ordering, CptError> {
+            // This function differentiates Cost metrics from Reward metrics:
+            // Example:
+            //      The Distance being a cost function, the ordering will be reversed (further means more cost)
+            //      The Similarity being a Reward function, the ordering will be preserved
+            match (self, other) {
+                (SimilarityScores::Similarity(self_value), SimilarityScores::Similarity(other_value)) => {
+                    if self_value == other_value { Ok(Ordering::Equal) }
+                    else if self_value > other_value { Ok(Ordering::Greater) }
+                    else { Ok(Ordering::Less) }
                 },
-                SimilarityScores::Length(self_value) => {
-                    match other {
-                        SimilarityScores::Length(other_value) => {
-                            if self_value == other_value { Ordering::Equal }
-                            else{
-                                if self_value > other_value{ Ordering::Greater }
-                                else{ Ordering::Less }
-                            }
-                        }
-                        _ => panic!("Cannot compare {:?} with {:?}", self, other)
-                    }
+                (SimilarityScores::Length(self_value), SimilarityScores::Length(other_value)) => {
+                    Ok(self_value.cmp(other_value))
                 },
-                SimilarityScores::Distance(self_value) => {
-                    match other {
-                        SimilarityScores::Distance(other_value) => {
-                            if self_value == other_value { Ordering::Equal }
-                            else{
-                                if self_value > other_value{ Ordering::Less }
-                                else{ Ordering::Greater }
-                            }
-                        }
-                        _ => panic!("Cannot compare {:?} with {:?}", self, other)
-                    }
+                (SimilarityScores::Distance(self_value), SimilarityScores::Distance(other_value)) => {
+                    if self_value == other_value { Ok(Ordering::Equal) }
+                    else if self_value > other_value { Ok(Ordering::Less) }
+                    else { Ok(Ordering::Greater) }
+                },
+                (SimilarityScores::IsEqual(self_value), SimilarityScores::IsEqual(other_value)) => {
+                    Ok(self_value.cmp(other_value))
                 },
-                _ => panic!("Cannot compare {:?} with {:?}", self, other)
+                _ => Err(CptError::IncompatibleTypes(format!("Cannot compare {:?} with {:?}", self, other)))
+            }
+        }
+
+        // Makes the score worse by `penalty`: distances grow and similarities shrink.
+        // Lengths and equality cannot be made worse by a fraction, only a zero penalty is accepted for them.
+        pub fn penalize(self, penalty: f32) -> Result<SimilarityScores, CptError> {
+            match self {
+                SimilarityScores::Distance(value) => Ok(SimilarityScores::Distance(value + penalty)),
+                SimilarityScores::Similarity(value) => Ok(SimilarityScores::Similarity(value - penalty)),
+                _ if penalty == 0.0 => Ok(self),
+                _ => Err(CptError::IncompatibleTypes(format!("Cannot apply a penalty of {} to {:?}", penalty, self)))
+            }
+        }
+
+        // The score as a number, true and false being 1 and 0.
+        pub fn value(self) -> f32 {
+            match self {
+                SimilarityScores::Similarity(value) | SimilarityScores::Distance(value) => value,
+                SimilarityScores::Length(value) => value as f32,
+                SimilarityScores::IsEqual(value) => if value { 1.0 } else { 0.0 },
+                SimilarityScores::None => 0.0
+            }
+        }
+
+        // The score as a number that is higher when better, used to weigh several scores.
+        pub fn utility(self) -> f32 {
+            match self {
+                SimilarityScores::Distance(value) => -value,
+                _ => self.value()
             }
         }
-    }
 
-    impl SimilarityScores{
         pub fn get_zero(self: Self) -> SimilarityScores {
+            self.try_get_zero().unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        pub fn try_get_zero(self) -> Result<SimilarityScores, CptError> {
             match self {
-                SimilarityScores::Similarity(_) => SimilarityScores::Similarity(0.0),
-                SimilarityScores::Distance(_) => SimilarityScores::Distance(0.0),
-                SimilarityScores::Length(_) => SimilarityScores::Length(0),
-                SimilarityScores::IsEqual(_) => SimilarityScores::IsEqual(true),
-                _ => panic!("No zero implemented for {:?}", self)
+                SimilarityScores::Similarity(_) => Ok(SimilarityScores::Similarity(0.0)),
+                SimilarityScores::Distance(_) => Ok(SimilarityScores::Distance(0.0)),
+                SimilarityScores::Length(_) => Ok(SimilarityScores::Length(0)),
+                SimilarityScores::IsEqual(_) => Ok(SimilarityScores::IsEqual(true)),
+                _ => Err(CptError::IncompatibleTypes(format!("No zero implemented for {:?}", self)))
+            }
+        }
+
+        pub fn try_add(self, other: Self) -> Result<SimilarityScores, CptError> {
+            match (self, other) {
+                (SimilarityScores::Similarity(x), SimilarityScores::Similarity(y)) => Ok(SimilarityScores::Similarity(x + y)),
+                (SimilarityScores::Distance(x), SimilarityScores::Distance(y)) => Ok(SimilarityScores::Distance(x + y)),
+                (SimilarityScores::Length(x), SimilarityScores::Length(y)) => Ok(SimilarityScores::Length(x + y)),
+                (SimilarityScores::IsEqual(x), SimilarityScores::IsEqual(y)) => Ok(SimilarityScores::IsEqual(x && y)),
+                _ => Err(CptError::IncompatibleTypes(format!("No addition function implemented between {:?} and {:?}", self, other)))
             }
         }
     }
@@ -124,41 +153,7 @@ pub mod data_types {
         type Output = Self;
 
         fn add(self, other: Self) -> Self {
-            match self {
-                SimilarityScores::Similarity(x) => {
-                    match other {
-                        SimilarityScores::Similarity(y) => {
-                            SimilarityScores::Similarity(x + y)
-                        },
-                        _ => panic!("No addition function implemented between {:?} and {:?}", self, other)
-                    }
-                },
-                SimilarityScores::Distance(x) => {
-                    match other {
-                        SimilarityScores::Distance(y) => {
-                            SimilarityScores::Distance(x + y)
-                        },
-                        _ => panic!("No addition function implemented between {:?} and {:?}", self, other)
-                    }
-                },
-                SimilarityScores::Length(x) => {
-                    match other {
-                        SimilarityScores::Length(y) => {
-                            SimilarityScores::Length(x + y)
-                        },
-                        _ => panic!("No addition function implemented between {:?} and {:?}", self, other)
-                    }
-                },
-                SimilarityScores::IsEqual(x) => {
-                    match other {
-                        SimilarityScores::IsEqual(y) => {
-                            SimilarityScores::IsEqual(x && y)
-                        },
-                        _ => panic!("No addition function implemented between {:?} and {:?}", self, other)
-                    }
-                },
-                _ => panic!("No addition function implemented between {:?} and {:?}", self, other)
-            }
+            self.try_add(other).unwrap_or_else(|e| panic!("{}", e))
         }
     }
 
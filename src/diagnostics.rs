@@ -0,0 +1,102 @@
+pub mod diagnostics {
+    use std::collections::HashMap;
+    use std::fmt::Debug as FmtDebug;
+    use std::fmt::Formatter as FmtFormatter;
+    use std::fmt::Result as FmtResult;
+    use std::sync::Arc;
+
+    use crate::cpt::cpt::{PredictedItem, SequenceMatchResult};
+    use crate::data_types::data_types::DataTypes;
+    use crate::nodes::nodes::NodeId;
+
+    // Intermediate state of `match_sequence` and `predict`, sent to the diagnostics hook of a CPT.
+    #[derive(Debug)]
+    pub enum DiagnosticEvent<'a> {
+        // A sequence is being matched against the tree
+        MatchSequence { sequence: &'a [DataTypes], backwards: bool },
+        // The matched sequences, sorted, with their aggregated scores
+        SequenceMatches { results: &'a [SequenceMatchResult] },
+        // A prediction starts, with the unique values of the prefix
+        PredictionPrefix { sequence: &'a [DataTypes], prefix_length: usize, prefix_values: &'a [DataTypes] },
+        // The training sequences, identified by their last node, containing a prefix value
+        PrefixValueSequences { prefix_value: DataTypes, node_count: usize, sequence_ids: &'a [NodeId] },
+        // The consequent of the query given a training sequence, as node ids
+        Consequent { sequence: &'a [DataTypes], sequence_id: NodeId, consequent: &'a [NodeId] },
+        // The support of each item found in the consequents
+        ConsequentSupport { sequence_ids: &'a [NodeId], support: &'a HashMap<DataTypes, usize> },
+        // The predicted items, sorted, with their support and confidence
+        Predictions { predictions: &'a [PredictedItem] },
+    }
+
+    // Receives the diagnostic events of a CPT. Closures taking a `&DiagnosticEvent` can be used as hooks.
+    pub trait DiagnosticsHook: Send + Sync {
+        fn on_event(&self, event: &DiagnosticEvent<'_>);
+    }
+
+    impl<F> DiagnosticsHook for F where F: Fn(&DiagnosticEvent<'_>) + Send + Sync {
+        fn on_event(&self, event: &DiagnosticEvent<'_>) {
+            self(event)
+        }
+    }
+
+    // Prints every event to stdout.
+    pub struct PrintDiagnostics;
+
+    impl DiagnosticsHook for PrintDiagnostics {
+        fn on_event(&self, event: &DiagnosticEvent<'_>) {
+            match event {
+                DiagnosticEvent::MatchSequence { sequence, backwards: true } => println!("Matching sequence backwards {:?}", sequence),
+                DiagnosticEvent::MatchSequence { sequence, backwards: false } => println!("Matching sequence forward {:?}", sequence),
+                DiagnosticEvent::SequenceMatches { results } => {
+                    println!("Matched sequences and scores:" );
+                    results.iter().for_each(|seq_score| println!("{:?}", seq_score) );
+                },
+                DiagnosticEvent::PredictionPrefix { sequence, prefix_length, prefix_values } => {
+                    println!("Looking for sequences in the training set with the last {:?} values in {:?}", prefix_length, sequence);
+                    println!("Prediction prefix unique values: {:?}", prefix_values);
+                },
+                DiagnosticEvent::PrefixValueSequences { prefix_value, node_count, sequence_ids } => {
+                    println!("Prefix unique value {:?} matched with {:?} nodes", prefix_value, node_count);
+                    println!("Matching sequences for prefix value {:?}: {:?}", prefix_value, sequence_ids);
+                },
+                DiagnosticEvent::Consequent { sequence, sequence_id, consequent } => {
+                    println!("Consequent for input sequence seq{:?} given training sequence {:?}: {:?}", sequence, sequence_id, consequent);
+                },
+                DiagnosticEvent::ConsequentSupport { sequence_ids, support } => {
+                    println!("Count of consequent's unique values among consequents: {:?}: {:?}", sequence_ids, support);
+                },
+                DiagnosticEvent::Predictions { predictions } => {
+                    println!("Sorted list of predicted items and their scores: {:?}", predictions);
+                },
+            }
+        }
+    }
+
+    // The diagnostics hook of a CPT, which does nothing unless a hook is set.
+    #[derive(Clone, Default)]
+    pub struct Diagnostics {
+        hook: Option<Arc<dyn DiagnosticsHook>>
+    }
+
+    impl Diagnostics {
+        pub fn new<H: DiagnosticsHook + 'static>(hook: H) -> Diagnostics {
+            Diagnostics { hook: Some(Arc::new(hook)) }
+        }
+
+        pub fn is_enabled(&self) -> bool {
+            self.hook.is_some()
+        }
+
+        pub fn emit(&self, event: DiagnosticEvent<'_>) {
+            if let Some(hook) = &self.hook {
+                hook.on_event(&event);
+            }
+        }
+    }
+
+    impl FmtDebug for Diagnostics {
+        fn fmt(&self, f: &mut FmtFormatter<'_>) -> FmtResult {
+            write!(f, "Diagnostics {{ enabled: {} }}", self.is_enabled())
+        }
+    }
+}
@@ -0,0 +1,192 @@
+pub mod dot {
+    // Graphviz exports focused on a query: only the nodes matched by match_sequence or used by
+    // predict are drawn, with the nodes around them up to a depth limit, so that large trees
+    // can still be rendered.
+    use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+    use crate::cpt::cpt::{CPT, MatchOptions, SequenceMatchFunction, TreeView};
+    use crate::data_types::data_types::DataTypes;
+    use crate::errors::errors::CptError;
+    use crate::nodes::nodes::NodeId;
+
+    // Options of the focused exports.
+    #[derive(Clone, Debug)]
+    pub struct DotOptions {
+        // Levels of ancestors and descendants drawn around the highlighted nodes
+        pub depth_limit: usize,
+        // Groups the ends of the drawn sequences by their first attribute
+        pub cluster_by_attributes: bool,
+    }
+
+    impl Default for DotOptions {
+        fn default() -> Self {
+            DotOptions { depth_limit: 2, cluster_by_attributes: false }
+        }
+    }
+
+    // How a node is drawn, a node keeps its strongest highlight
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    enum Highlight {
+        Context,
+        Matched,
+        Consequent,
+        Predicted,
+    }
+
+    impl Highlight {
+        fn fill_color(self) -> &'static str {
+            match self {
+                Highlight::Context => "white",
+                Highlight::Matched => "lightblue",
+                Highlight::Consequent => "lightyellow",
+                Highlight::Predicted => "orange",
+            }
+        }
+    }
+
+    // The highlighted nodes with their labels, and the notes pointing at them
+    #[derive(Default)]
+    struct FocusedGraph {
+        highlights: BTreeMap<NodeId, Highlight>,
+        labels: BTreeMap<NodeId, Vec<String>>,
+        notes: Vec<(String, Option<NodeId>)>,
+    }
+
+    impl FocusedGraph {
+        fn highlight(&mut self, node_id: NodeId, highlight: Highlight, label: Option<String>) {
+            let current = self.highlights.entry(node_id).or_insert(highlight);
+            *current = (*current).max(highlight);
+            if let Some(label) = label {
+                let labels = self.labels.entry(node_id).or_default();
+                if !labels.contains(&label) {
+                    labels.push(label);
+                }
+            }
+        }
+    }
+
+    impl CPT<DataTypes> {
+        // Draws the matches of `match_sequence_with_options`: the matched nodes with their
+        // scores, and a note with the total scores of each match.
+        pub fn match_to_dot(&self, sequence: &[DataTypes], backwards: bool, match_functions: &[SequenceMatchFunction], match_options: &MatchOptions, options: &DotOptions) -> Result<String, CptError> {
+            let mut graph = FocusedGraph::default();
+            // The best matches are last, they are numbered first
+            for (rank, (matched_nodes, scores)) in self.match_sequence_with_options(sequence, backwards, match_functions, match_options)?.iter().rev().enumerate() {
+                for (node_id, node_scores) in matched_nodes.iter() {
+                    let node_scores: Vec<String> = node_scores.iter().map(|(function, score)| format!("{:?}: {:?}", function, score)).collect();
+                    graph.highlight(*node_id, Highlight::Matched, Some(format!("match {}: {}", rank, node_scores.join(", "))));
+                }
+                let total_scores: Vec<String> = match_functions.iter()
+                    .filter_map(|function| scores.get(function).map(|score| format!("{:?}: {:?}", function, score)))
+                    .collect();
+                graph.notes.push((format!("match {}\n{}", rank, total_scores.join("\n")), matched_nodes.last().map(|node| node.0)));
+            }
+            Ok(self.focused_dot(&graph, options))
+        }
+
+        // Draws what `predict` used: the nodes of the training sequences sharing the prefix
+        // items, their consequents, with the support and confidence of their items, and the
+        // nodes of the best predicted item.
+        pub fn predict_to_dot(&self, sequence: &[DataTypes], prefix_length: usize, options: &DotOptions) -> Result<String, CptError> {
+            let (predictions, consequents) = TreeView::try_predict_with_consequents(self, sequence, prefix_length)?;
+
+            let mut graph = FocusedGraph::default();
+            for (last_node_id, consequent) in consequents {
+                // The consequent stops below the node holding an item of the query, the end of
+                // the sequence when the consequent is empty
+                let matched_node_id = match consequent.first() {
+                    Some(&first_node_id) => self.nodes[first_node_id].parent,
+                    None => Some(last_node_id)
+                };
+                if let Some(matched_node_id) = matched_node_id.filter(|&node_id| self.nodes[node_id].data.is_some()) {
+                    graph.highlight(matched_node_id, Highlight::Matched, None);
+                }
+                for node_id in consequent {
+                    let prediction = self.nodes[node_id].data.and_then(|node_data| predictions.iter().position(|prediction| prediction.0 == node_data));
+                    let highlight = if prediction == Some(0) { Highlight::Predicted } else { Highlight::Consequent };
+                    graph.highlight(node_id, highlight, prediction.map(|rank| {
+                        let (_item, support, confidence) = predictions[rank];
+                        format!("prediction {}: support {}, confidence {:.3}", rank, support, confidence)
+                    }));
+                }
+            }
+            let prediction_lines: Vec<String> = predictions.iter().enumerate()
+                .map(|(rank, (item, support, confidence))| format!("{}: {:?} (support {}, confidence {:.3})", rank, item, support, confidence))
+                .collect();
+            graph.notes.push((format!("predictions\n{}", prediction_lines.join("\n")), None));
+            Ok(self.focused_dot(&graph, options))
+        }
+
+        fn focused_dot(&self, graph: &FocusedGraph, options: &DotOptions) -> String {
+            // The highlighted nodes, and their ancestors and descendants up to the depth limit
+            let mut drawn_node_ids: BTreeSet<NodeId> = graph.highlights.keys().copied().collect();
+            for &node_id in graph.highlights.keys() {
+                let mut ancestor_node_id = node_id;
+                for _ in 0..options.depth_limit {
+                    match self.nodes[ancestor_node_id].parent {
+                        Some(parent_node_id) => {
+                            drawn_node_ids.insert(parent_node_id);
+                            ancestor_node_id = parent_node_id;
+                        },
+                        None => break
+                    }
+                }
+                let mut level = vec![node_id];
+                for _ in 0..options.depth_limit {
+                    level = level.iter().flat_map(|&id| self.nodes[id].children.iter().copied()).collect();
+                    drawn_node_ids.extend(level.iter().copied());
+                }
+            }
+
+            let mut dot_string = String::from("digraph Focus {\n");
+            let mut clustered_node_ids = HashSet::<NodeId>::new();
+            if options.cluster_by_attributes {
+                let mut clusters = BTreeMap::<String, Vec<NodeId>>::new();
+                for &node_id in drawn_node_ids.iter() {
+                    if let Some(attribute) = self.sequences_lookup_table.get(&node_id).and_then(|attributes| attributes.first()) {
+                        clusters.entry(format!("{:?}", attribute)).or_default().push(node_id);
+                    }
+                }
+                for (index, (attribute, node_ids)) in clusters.iter().enumerate() {
+                    dot_string.push_str(&format!("subgraph cluster_attr{} {{\nlabel=\"{}\";\n", index, escape(attribute)));
+                    for &node_id in node_ids {
+                        dot_string.push_str(&self.dot_node(graph, node_id));
+                        clustered_node_ids.insert(node_id);
+                    }
+                    dot_string.push_str("}\n");
+                }
+            }
+            for &node_id in drawn_node_ids.iter().filter(|node_id| !clustered_node_ids.contains(node_id)) {
+                dot_string.push_str(&self.dot_node(graph, node_id));
+            }
+            for &node_id in drawn_node_ids.iter() {
+                for child_node_id in self.nodes[node_id].children.iter().filter(|child_node_id| drawn_node_ids.contains(child_node_id)) {
+                    dot_string.push_str(&format!("{} -> {};\n", node_id, child_node_id));
+                }
+            }
+            for (index, (note, node_id)) in graph.notes.iter().enumerate() {
+                dot_string.push_str(&format!("note{}[label=\"{}\"; shape=\"rectangle\"];\n", index, escape(note)));
+                if let Some(node_id) = node_id {
+                    dot_string.push_str(&format!("note{} -> {} [style=dashed];\n", index, node_id));
+                }
+            }
+            dot_string.push_str("}\n");
+            dot_string
+        }
+
+        fn dot_node(&self, graph: &FocusedGraph, node_id: NodeId) -> String {
+            let mut label = format!("ID={:?}, {:?}", node_id, self.nodes[node_id].data);
+            graph.labels.get(&node_id).into_iter().flatten().for_each(|line| {
+                label.push('\n');
+                label.push_str(line);
+            });
+            let highlight = graph.highlights.get(&node_id).copied().unwrap_or(Highlight::Context);
+            format!("{}[label=\"{}\"; style=filled; fillcolor=\"{}\"];\n", node_id, escape(&label), highlight.fill_color())
+        }
+    }
+
+    // Escapes a label for a quoted DOT string
+    fn escape(label: &str) -> String {
+        label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+    }
+}
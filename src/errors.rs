@@ -0,0 +1,84 @@
+pub mod errors {
+    use std::cell::RefCell;
+    use std::fmt::Display as FmtDisplay;
+    use std::fmt::Formatter as FmtFormatter;
+    use std::fmt::Result as FmtResult;
+
+    use crate::nodes::nodes::NodeId;
+
+    #[derive(Debug)]
+    pub enum CptError {
+        // Reading or writing a model failed
+        Io(std::io::Error),
+        // The model is not valid JSON, or does not have the CPT structure
+        Json(serde_json::Error),
+        // The model was read, but its content is inconsistent
+        CorruptModel(String),
+        // Two values or scores of different kinds were compared or added
+        IncompatibleTypes(String),
+        // No node exists with this id
+        UnknownNode(NodeId),
+        // The prefix used for a prediction is longer than the query sequence
+        InvalidPrefix { prefix_length: usize, sequence_length: usize },
+        // A compact model cannot address this many nodes with 32-bit ids
+        TooManyNodes(usize),
+        // The ranking policy does not fit the metrics of the query
+        InvalidRankingPolicy(String),
+        // A pattern query could not be parsed
+        InvalidPattern(String),
+    }
+
+    // Keeps the first error met inside a closure that cannot fail, such as a sort comparator,
+    // and returns `fallback` in place of the failed value. The caller returns the kept error afterwards.
+    pub(crate) fn keep_first_error<T>(first_error: &RefCell<Option<CptError>>, result: Result<T, CptError>, fallback: impl FnOnce() -> T) -> T {
+        result.unwrap_or_else(|e| {
+            first_error.borrow_mut().get_or_insert(e);
+            fallback()
+        })
+    }
+
+    impl FmtDisplay for CptError {
+        fn fmt(&self, f: &mut FmtFormatter<'_>) -> FmtResult {
+            match self {
+                CptError::Io(e) => write!(f, "I/O error: {}", e),
+                CptError::Json(e) => write!(f, "JSON error: {}", e),
+                CptError::CorruptModel(message) => write!(f, "Corrupt model: {}", message),
+                CptError::IncompatibleTypes(message) => write!(f, "Incompatible types: {}", message),
+                CptError::UnknownNode(id) => write!(f, "No node found for NodeId {}", id),
+                CptError::InvalidPrefix { prefix_length, sequence_length } => {
+                    write!(f, "Prefix length {} is longer than the sequence length {}", prefix_length, sequence_length)
+                },
+                CptError::TooManyNodes(count) => write!(f, "{} nodes cannot be addressed with 32-bit node ids", count),
+                CptError::InvalidRankingPolicy(message) => write!(f, "Invalid ranking policy: {}", message),
+                CptError::InvalidPattern(message) => write!(f, "Invalid pattern: {}", message),
+            }
+        }
+    }
+
+    impl std::error::Error for CptError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match self {
+                CptError::Io(e) => Some(e),
+                CptError::Json(e) => Some(e),
+                _ => None
+            }
+        }
+    }
+
+    impl From<std::io::Error> for CptError {
+        fn from(e: std::io::Error) -> Self {
+            // The binary reader reports corrupt data as InvalidData
+            if e.kind() == std::io::ErrorKind::InvalidData {
+                CptError::CorruptModel(e.to_string())
+            } else {
+                CptError::Io(e)
+            }
+        }
+    }
+
+    impl From<serde_json::Error> for CptError {
+        fn from(e: serde_json::Error) -> Self {
+            CptError::Json(e)
+        }
+    }
+}
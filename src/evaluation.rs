@@ -0,0 +1,93 @@
+pub mod evaluation {
+    use std::io::BufRead;
+    use serde::{Serialize, Deserialize};
+
+    use crate::data_types::data_types::DataTypes;
+    use crate::errors::errors::CptError;
+    use crate::predictors::predictors::SequencePredictor;
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct EvaluationResult {
+        pub predictor: String,
+        // Number of testing sequences long enough to be queried
+        pub queries: usize,
+        // The expected item was among the top_k predictions
+        pub success: usize,
+        // Predictions were made, but the expected item was not among them
+        pub failure: usize,
+        // The predictor had nothing to say about the query
+        pub no_match: usize,
+        pub accuracy: f32,
+        pub coverage: f32,
+    }
+
+    // Reads one sequence of integers per line, separated by spaces or commas.
+    // Empty lines and lines starting with `#` are ignored, and SPMF files are
+    // supported as well: `-1` separators are skipped and `-2` ends the sequence.
+    pub fn read_sequences<R: BufRead>(reader: R) -> std::io::Result<Vec<Vec<DataTypes>>> {
+        let mut sequences = Vec::<Vec<DataTypes>>::new();
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut sequence = Vec::<DataTypes>::new();
+            for token in line.split(|c: char| c.is_whitespace() || c == ',').filter(|token| !token.is_empty()) {
+                match token {
+                    "-1" => continue,
+                    "-2" => break,
+                    _ => sequence.push(DataTypes::Integer(token.parse::<usize>().map_err(|e| {
+                        std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Invalid item {:?}: {}", token, e))
+                    })?))
+                }
+            }
+            sequences.push(sequence);
+        }
+        Ok(sequences)
+    }
+
+    // Splits the sequences into a training and a testing set: the last
+    // `test_ratio` of the sequences are used for testing.
+    pub fn split_dataset(sequences: &[Vec<DataTypes>], test_ratio: f32) -> (&[Vec<DataTypes>], &[Vec<DataTypes>]) {
+        let test_count = ((sequences.len() as f32) * test_ratio.clamp(0.0, 1.0)).round() as usize;
+        sequences.split_at(sequences.len() - test_count)
+    }
+
+    // Trains the predictor on every training sequence.
+    pub fn train<P: SequencePredictor + ?Sized>(predictor: &mut P, training: &[Vec<DataTypes>]) {
+        training.iter().for_each(|sequence| predictor.train(sequence));
+    }
+
+    // Queries the predictor with every testing sequence but its last item,
+    // and checks whether that last item is among the `top_k` predictions.
+    // Stops at the first prediction error.
+    pub fn evaluate<P: SequencePredictor + ?Sized>(predictor: &P, testing: &[Vec<DataTypes>], prefix_length: usize, top_k: usize) -> Result<EvaluationResult, CptError> {
+        let mut result = EvaluationResult {
+            predictor: predictor.name().to_string(),
+            queries: 0,
+            success: 0,
+            failure: 0,
+            no_match: 0,
+            accuracy: 0.0,
+            coverage: 0.0,
+        };
+        for sequence in testing.iter().filter(|sequence| sequence.len() > 1) {
+            let (query, expected) = sequence.split_at(sequence.len() - 1);
+            let predictions = predictor.try_predict_next(query, prefix_length)?;
+            result.queries += 1;
+            if predictions.is_empty() {
+                result.no_match += 1;
+            } else if predictions.iter().take(top_k).any(|&(item, _score)| item == expected[0]) {
+                result.success += 1;
+            } else {
+                result.failure += 1;
+            }
+        }
+        if result.queries > 0 {
+            result.accuracy = result.success as f32 / result.queries as f32;
+            result.coverage = (result.queries - result.no_match) as f32 / result.queries as f32;
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,196 @@
+pub mod frozen {
+    // Immutable layout of a trained CPT<DataTypes>, meant for serving predictions.
+    //
+    // Nodes keep the ids they had in the CPT, and every per node field is a flat array:
+    //  data            the data of each node, None for the root
+    //  parents         the parent of each node, NO_PARENT for the root
+    //  children        the children of all nodes, stored contiguously and sorted by data,
+    //                  the children of node i are children[child_offsets[i]..child_offsets[i + 1]]
+    // The inverted index is stored the same way: the nodes holding values[i] are
+    // node_ids[offsets[i]..offsets[i + 1]].
+    use crate::cpt::cpt::{CPT, MatchOptions, PredictedItem, SequenceMatchFunction, SequenceMatchResult, TreeView, ValueIndex};
+    use crate::data_types::data_types::{DataTypes, SequenceAttributes};
+    use crate::diagnostics::diagnostics::{Diagnostics, DiagnosticsHook};
+    use crate::errors::errors::CptError;
+    use crate::metrics::metrics::{MetricMatchResult, SimilarityMetric};
+    use crate::nodes::nodes::NodeId;
+
+    // Parent of the root in the parents array
+    const NO_PARENT: NodeId = NodeId::MAX;
+
+    #[derive(Debug, Clone)]
+    pub(crate) struct FrozenIndex {
+        values: Vec<DataTypes>,
+        offsets: Vec<usize>,
+        node_ids: Vec<NodeId>,
+    }
+
+    impl ValueIndex for FrozenIndex {
+        fn values(&self) -> &[DataTypes] {
+            &self.values
+        }
+
+        fn node_ids_at(&self, position: usize) -> impl ExactSizeIterator<Item = NodeId> + '_ {
+            self.node_ids[self.offsets[position]..self.offsets[position + 1]].iter().copied()
+        }
+    }
+
+    // A read-only copy of a CPT, built by `CPT::freeze`. It supports the same queries
+    // as the CPT it was built from, with fewer allocations to follow, but cannot be trained.
+    #[derive(Debug, Clone)]
+    pub struct FrozenCPT {
+        data: Vec<Option<DataTypes>>,
+        parents: Vec<NodeId>,
+        child_offsets: Vec<usize>,
+        children: Vec<NodeId>,
+        index: FrozenIndex,
+        // Last node of each sequence, sorted, and the attributes of the sequences
+        sequence_ends: Vec<NodeId>,
+        sequence_attributes: Vec<Vec<SequenceAttributes>>,
+        pub diagnostics: Diagnostics
+    }
+
+    impl CPT<DataTypes> {
+        // Copies the tree into an immutable `FrozenCPT`. The diagnostics hook is kept.
+        pub fn freeze(&self) -> FrozenCPT {
+            let mut child_offsets = Vec::<usize>::with_capacity(self.nodes.len() + 1);
+            let mut children = Vec::<NodeId>::with_capacity(self.nodes.len().saturating_sub(1));
+            child_offsets.push(0);
+            for node in self.nodes.iter() {
+                let start = children.len();
+                children.extend(node.children.iter().copied());
+                // Sorting the children by data allows a binary search in FrozenCPT::child
+                children[start..].sort_by_key(|&child_id| self.nodes.get(child_id).and_then(|child| child.data));
+                child_offsets.push(children.len());
+            }
+
+            let mut offsets = Vec::<usize>::with_capacity(self.inverted_index.values.len() + 1);
+            let mut node_ids = Vec::<NodeId>::with_capacity(self.nodes.len().saturating_sub(1));
+            offsets.push(0);
+            for value_node_ids in self.inverted_index.node_ids.iter() {
+                node_ids.extend(value_node_ids.iter().copied());
+                offsets.push(node_ids.len());
+            }
+
+            let mut sequences: Vec<(&NodeId, &Vec<SequenceAttributes>)> = self.sequences_lookup_table.iter().collect();
+            sequences.sort_by_key(|(&last_node_id, _)| last_node_id);
+
+            FrozenCPT {
+                data: self.nodes.iter().map(|node| node.data).collect(),
+                parents: self.nodes.iter().map(|node| node.parent.unwrap_or(NO_PARENT)).collect(),
+                child_offsets,
+                children,
+                index: FrozenIndex { values: self.inverted_index.values.clone(), offsets, node_ids },
+                sequence_ends: sequences.iter().map(|(&last_node_id, _)| last_node_id).collect(),
+                sequence_attributes: sequences.into_iter().map(|(_, attributes)| attributes.clone()).collect(),
+                diagnostics: self.diagnostics.clone()
+            }
+        }
+    }
+
+    impl FrozenCPT {
+        // Sends the intermediate state of `match_sequence` and `predict` to the given hook.
+        pub fn set_diagnostics<H: DiagnosticsHook + 'static>(&mut self, hook: H) {
+            self.diagnostics = Diagnostics::new(hook);
+        }
+
+        // Number of nodes in the tree, including the root.
+        pub fn node_count(&self) -> usize {
+            self.data.len()
+        }
+
+        pub fn get_data(&self, id: NodeId) -> Option<DataTypes> {
+            self.try_get_data(id).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        pub fn try_get_data(&self, id: NodeId) -> Result<Option<DataTypes>, CptError> {
+            self.data.get(id).copied().ok_or(CptError::UnknownNode(id))
+        }
+
+        pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+            self.parents.get(id).copied().filter(|&parent_id| parent_id != NO_PARENT)
+        }
+
+        // The children of a node, sorted by data. Unknown nodes have no children.
+        pub fn children(&self, id: NodeId) -> &[NodeId] {
+            if id < self.data.len() {
+                &self.children[self.child_offsets[id]..self.child_offsets[id + 1]]
+            } else { &[] }
+        }
+
+        // Returns the child of a node holding the given data, using a binary search.
+        // A child without data can only come from an inconsistent model, it is reported as corrupt.
+        pub fn child(&self, id: NodeId, data: DataTypes) -> Result<Option<NodeId>, CptError> {
+            let children = self.children(id);
+            // The children without data are sorted first
+            if let Some(&child_id) = children.first().filter(|&&child_id| self.node_data(child_id).is_none()) {
+                return Err(CptError::CorruptModel(format!("Child {} of node {} has no data", child_id, id)));
+            }
+            Ok(children.binary_search_by(|&child_id| self.node_data(child_id).cmp(&Some(data)))
+                .ok()
+                .map(|position| children[position]))
+        }
+
+        // The attributes of the sequence ending at a node, None if no sequence ends there.
+        pub fn sequence_attributes(&self, last_node_id: NodeId) -> Option<&[SequenceAttributes]> {
+            self.sequence_ends.binary_search(&last_node_id)
+                .ok()
+                .map(|position| self.sequence_attributes[position].as_slice())
+        }
+
+        pub fn match_sequence(&self, sequence: &[DataTypes], backwards: bool, match_functions: &[SequenceMatchFunction]) -> Vec<SequenceMatchResult> {
+            self.match_sequence_with_options(sequence, backwards, match_functions, &MatchOptions::default()).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        pub fn match_sequence_with_options(&self, sequence: &[DataTypes], backwards: bool, match_functions: &[SequenceMatchFunction], options: &MatchOptions) -> Result<Vec<SequenceMatchResult>, CptError> {
+            TreeView::match_sequence(self, sequence, backwards, match_functions, options)
+        }
+
+        pub fn match_sequence_with_metrics(&self, sequence: &[DataTypes], backwards: bool, metrics: &[&dyn SimilarityMetric], options: &MatchOptions) -> Result<Vec<MetricMatchResult>, CptError> {
+            TreeView::match_sequence_with_metrics(self, sequence, backwards, metrics, options)
+        }
+
+        pub fn predict(&self, sequence: &[DataTypes], prefix_length: usize) -> Vec<PredictedItem> {
+            self.try_predict(sequence, prefix_length).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        pub fn try_predict(&self, sequence: &[DataTypes], prefix_length: usize) -> Result<Vec<PredictedItem>, CptError> {
+            TreeView::try_predict(self, sequence, prefix_length)
+        }
+
+        // Returns the training sequences, identified by their last node, containing the given value.
+        pub fn prefix_value_sequence_ids(&self, prefix_value: DataTypes) -> Result<Vec<NodeId>, CptError> {
+            TreeView::prefix_value_sequence_ids(self, prefix_value)
+        }
+    }
+
+    impl TreeView for FrozenCPT {
+        type Index = FrozenIndex;
+
+        fn index(&self) -> &FrozenIndex {
+            &self.index
+        }
+
+        fn node_data(&self, id: NodeId) -> Option<DataTypes> {
+            self.data.get(id).copied().flatten()
+        }
+
+        fn node_parent(&self, id: NodeId) -> Option<NodeId> {
+            self.parent(id)
+        }
+
+        fn node_children(&self, id: NodeId) -> Option<impl Iterator<Item = NodeId> + '_> {
+            if id < self.data.len() {
+                Some(self.children(id).iter().copied())
+            } else { None }
+        }
+
+        fn is_sequence_end(&self, id: NodeId) -> bool {
+            self.sequence_ends.binary_search(&id).is_ok()
+        }
+
+        fn diagnostics(&self) -> &Diagnostics {
+            &self.diagnostics
+        }
+    }
+}
@@ -1,3 +1,20 @@
 pub mod data_types;
 pub mod nodes;
-pub mod cpt;
\ No newline at end of file
+pub mod cpt;
+pub mod binary;
+pub mod persistence;
+pub mod errors;
+pub mod diagnostics;
+pub mod concurrent;
+pub mod predictors;
+pub mod evaluation;
+pub mod frozen;
+pub mod compact;
+pub mod alignment;
+pub mod metrics;
+pub mod ranking;
+pub mod pattern;
+pub mod subtrees;
+pub mod mining;
+pub mod stats;
+pub mod dot;
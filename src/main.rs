@@ -1,12 +1,155 @@
 
-use std::io::{ Read, Write };
-use cpt_rust::data_types::data_types::{DataTypes, SimilarityScores, SequenceAttributes};
+use std::io::{ BufReader, Write };
+use cpt_rust::data_types::data_types::{DataTypes, SequenceAttributes};
 use cpt_rust::cpt::cpt::CPT;
 use cpt_rust::cpt::cpt::SequenceMatchFunction as SequenceMatchFunction;
+use cpt_rust::diagnostics::diagnostics::PrintDiagnostics;
+use cpt_rust::evaluation::evaluation;
+use cpt_rust::pattern::pattern::Pattern;
+use cpt_rust::persistence::persistence::ModelFormat;
+use cpt_rust::predictors::predictors::{predictor_from_name, PREDICTOR_NAMES};
 
 use std::fs::File;
 
-fn main() -> std::io::Result<()> {
+const USAGE: &str = "Usage:
+    cpt-rust                    Run the demo and write the CPT to test.dot
+    cpt-rust evaluate <file> [--models cpt,markov,akom,ppm,dg,popularity,hybrid] [--prefix-length N] [--top-k N] [--test-ratio R]
+                                Train each model on the sequences of <file> and compare their accuracy
+    cpt-rust train <file> <model file> [--format json|binary] [--threads N]
+                                Train a CPT on the sequences of <file> and save it
+    cpt-rust predict <model file> <item>... [--prefix-length N]
+                                Load a saved CPT and predict the items following the given sequence
+    cpt-rust query <model file> '<pattern>'
+                                Load a saved CPT and list the training sequences matching a pattern like '^ 1 * [4..6] ?',
+                                quote the pattern so that the shell does not expand * and ?
+    cpt-rust stats <model file>
+                                Load a saved CPT and print the statistics of the tree as JSON";
+
+type CliResult = Result<(), Box<dyn std::error::Error>>;
+
+fn main() -> CliResult {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(|command| command.as_str()) {
+        None => demo(),
+        Some("evaluate") => evaluate(&args[1..]),
+        Some("train") => train(&args[1..]),
+        Some("predict") => predict(&args[1..]),
+        Some("query") => query(&args[1..]),
+        Some("stats") => stats(&args[1..]),
+        Some(_) => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn invalid_input(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{}\n{}", message, USAGE))
+}
+
+fn parse_option<T: std::str::FromStr>(name: &str, value: Option<&String>) -> std::io::Result<T> {
+    value.and_then(|value| value.parse::<T>().ok())
+        .ok_or_else(|| invalid_input(format!("Invalid value for {}", name)))
+}
+
+fn evaluate(args: &[String]) -> CliResult {
+    // Compare the CPT and the baseline models on the same training and testing sets
+    let mut models: Vec<String> = PREDICTOR_NAMES.iter().map(|name| name.to_string()).collect();
+    let mut prefix_length: usize = 3;
+    let mut top_k: usize = 1;
+    let mut test_ratio: f32 = 0.2;
+    let mut path: Option<&String> = None;
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--models" => models = parse_option::<String>(arg, args_iter.next())?.split(',').map(|name| name.to_string()).collect(),
+            "--prefix-length" => prefix_length = parse_option(arg, args_iter.next())?,
+            "--top-k" => top_k = parse_option(arg, args_iter.next())?,
+            "--test-ratio" => test_ratio = parse_option(arg, args_iter.next())?,
+            _ => path = Some(arg)
+        }
+    }
+    let path = path.ok_or_else(|| invalid_input("Missing sequences file".to_string()))?;
+    let sequences = evaluation::read_sequences(BufReader::new(File::open(path)?))?;
+    let (training, testing) = evaluation::split_dataset(&sequences, test_ratio);
+
+    println!("{:<10} {:>8} {:>8} {:>8} {:>8} {:>9} {:>9}", "model", "queries", "success", "failure", "no match", "accuracy", "coverage");
+    for model in models {
+        let mut predictor = predictor_from_name(&model)
+            .ok_or_else(|| invalid_input(format!("Unknown model {:?}", model)))?;
+        evaluation::train(predictor.as_mut(), training);
+        let result = evaluation::evaluate(predictor.as_ref(), testing, prefix_length, top_k)?;
+        println!("{:<10} {:>8} {:>8} {:>8} {:>8} {:>9.4} {:>9.4}",
+            result.predictor, result.queries, result.success, result.failure, result.no_match, result.accuracy, result.coverage);
+    }
+    Ok(())
+}
+
+fn train(args: &[String]) -> CliResult {
+    let mut format = ModelFormat::Binary;
+    let mut threads: usize = 1;
+    let mut paths = Vec::<&String>::new();
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--format" => format = ModelFormat::from_name(&parse_option::<String>(arg, args_iter.next())?)
+                .ok_or_else(|| invalid_input("Unknown model format".to_string()))?,
+            "--threads" => threads = parse_option(arg, args_iter.next())?,
+            _ => paths.push(arg)
+        }
+    }
+    if paths.len() != 2 {
+        return Err(invalid_input("Expected a sequences file and a model file".to_string()).into());
+    }
+    let sequences = evaluation::read_sequences(BufReader::new(File::open(paths[0])?))?;
+    let cpt = CPT::train_parallel(sequences.into_iter().map(|sequence| (sequence, None)).collect(), threads)?;
+    cpt.save_file(paths[1], format)?;
+    Ok(())
+}
+
+fn predict(args: &[String]) -> CliResult {
+    let mut prefix_length: Option<usize> = None;
+    let mut model_path: Option<&String> = None;
+    let mut sequence = Vec::<DataTypes>::new();
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--prefix-length" => prefix_length = Some(parse_option(arg, args_iter.next())?),
+            _ if model_path.is_none() => model_path = Some(arg),
+            _ => sequence.push(DataTypes::Integer(parse_option(arg, Some(arg))?))
+        }
+    }
+    let model_path = model_path.ok_or_else(|| invalid_input("Missing model file".to_string()))?;
+    let cpt = CPT::load_file(model_path)?;
+    let prefix_length = prefix_length.unwrap_or(sequence.len()).min(sequence.len());
+    for (item, support, confidence) in cpt.try_predict(&sequence, prefix_length)? {
+        println!("{:?}\t{}\t{}", item, support, confidence);
+    }
+    Ok(())
+}
+
+fn query(args: &[String]) -> CliResult {
+    // The pattern may be given as one argument or as one argument per item
+    let model_path = args.first().ok_or_else(|| invalid_input("Missing model file".to_string()))?;
+    let pattern = Pattern::parse(&args[1..].join(" "))?;
+    let cpt = CPT::load_file(model_path)?;
+    for pattern_match in cpt.query(&pattern) {
+        println!("{}\t{:?}\t{:?}\t{:?}", pattern_match.last_node_id, pattern_match.positions, pattern_match.sequence, pattern_match.attributes);
+    }
+    Ok(())
+}
+
+fn stats(args: &[String]) -> CliResult {
+    let model_path = args.first().ok_or_else(|| invalid_input("Missing model file".to_string()))?;
+    let cpt = CPT::load_file(model_path)?;
+    println!("{}", cpt.stats().to_json_pretty());
+    Ok(())
+}
+
+fn demo() -> CliResult {
     // let seq1: [crate::DataTypes; 6] = [DataTypes::Integer(1), DataTypes::Integer(2), DataTypes::Integer(3), DataTypes::Integer(4), DataTypes::Integer(5), DataTypes::Integer(5)];
     // let seq2: [crate::DataTypes; 6] = [DataTypes::Integer(2), DataTypes::Integer(2), DataTypes::Integer(3), DataTypes::Integer(4), DataTypes::Integer(5), DataTypes::Integer(5)];
     
@@ -19,6 +162,7 @@ fn main() -> std::io::Result<()> {
     // let seq2: [DataTypes; 3] = [DataTypes::Integer(2), DataTypes::Integer(3), DataTypes::Integer(3)];
     
     let mut cpt = CPT::new();
+    cpt.set_diagnostics(PrintDiagnostics);
 
     cpt.add_sequence_to_root(seq1.to_vec(), None);
     cpt.add_sequence_to_root(seq2.to_vec(),
@@ -66,6 +210,6 @@ fn main() -> std::io::Result<()> {
     // cpt.add_sequence_to_root( buffer.iter().map(|&byte| DataTypes::U8(byte)).collect(), None );
 
     let mut file = File::create("test.dot")?;
-    file.write(cpt.to_dot().as_bytes())?;
+    file.write_all(cpt.to_dot().as_bytes())?;
     Ok(())
 }
\ No newline at end of file
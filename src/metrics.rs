@@ -0,0 +1,74 @@
+pub mod metrics {
+    use std::cmp::Ordering;
+
+    use crate::cpt::cpt::SequenceMatchFunction;
+    use crate::data_types::data_types::DataTypes;
+    use crate::errors::errors::CptError;
+    use crate::nodes::nodes::NodeId;
+
+    // A matched node with its score for each metric
+    pub type MetricNodeMatch = (NodeId, Vec<f32>);
+    // A matched sequence with the aggregated score of each metric
+    pub type MetricMatchResult = (Vec<MetricNodeMatch>, Vec<f32>);
+
+    // Whether a lower or a higher score is better.
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    pub enum ScoreKind {
+        // Lower is better, like a distance
+        Cost,
+        // Higher is better, like a similarity
+        Reward,
+    }
+
+    // Scores a node value against a query value. The scores of the items of a matched
+    // sequence are aggregated, starting from `zero`. Implement it for domain specific
+    // similarities and pass it to `match_sequence_with_metrics`.
+    pub trait SimilarityMetric: Send + Sync {
+        // Name of the metric, for reports and diagnostics.
+        fn name(&self) -> String;
+
+        fn kind(&self) -> ScoreKind;
+
+        fn score(&self, node_value: DataTypes, query_value: DataTypes) -> Result<f32, CptError>;
+
+        fn zero(&self) -> f32 {
+            0.0
+        }
+
+        fn aggregate(&self, total: f32, score: f32) -> f32 {
+            total + score
+        }
+
+        // Orders two scores, the better score being Greater.
+        fn compare(&self, a: f32, b: f32) -> Ordering {
+            let ordering = a.partial_cmp(&b).unwrap_or(Ordering::Equal);
+            match self.kind() {
+                ScoreKind::Cost => ordering.reverse(),
+                ScoreKind::Reward => ordering,
+            }
+        }
+    }
+
+    // The built-in item functions, sequence metrics cannot score single items
+    impl SimilarityMetric for SequenceMatchFunction {
+        fn name(&self) -> String {
+            format!("{:?}", self)
+        }
+
+        fn kind(&self) -> ScoreKind {
+            match self {
+                SequenceMatchFunction::AlgebraicDistance
+                | SequenceMatchFunction::EditDistance(_)
+                | SequenceMatchFunction::DynamicTimeWarping => ScoreKind::Cost,
+                _ => ScoreKind::Reward,
+            }
+        }
+
+        fn score(&self, node_value: DataTypes, query_value: DataTypes) -> Result<f32, CptError> {
+            match self {
+                SequenceMatchFunction::StrictEqual => Ok(if node_value == query_value { 1.0 } else { 0.0 }),
+                _ => Ok(node_value.try_compute_similarity(*self, query_value)?.value())
+            }
+        }
+    }
+}
@@ -0,0 +1,270 @@
+pub mod mining {
+    // Frequent patterns of the training sequences, mined from the tree rather than from the raw data.
+    //
+    // A pattern occurs at nodes of the tree: the nodes holding its last item. The sequences
+    // containing the pattern are the sequences going through one of these nodes. Keeping only
+    // the occurrences that have no other occurrence above them, the support is the sum of the
+    // sequences going through each of them, read from the `SubtreeIndex`.
+    //
+    // Sequential rules are counted on the training sequences read back from the tree,
+    // growing their antecedents one item at a time.
+    use std::collections::{HashMap, HashSet};
+    use std::fmt::Display as FmtDisplay;
+    use std::fmt::Formatter as FmtFormatter;
+    use std::fmt::Result as FmtResult;
+    use serde::Serialize;
+
+    use crate::cpt::cpt::CPT;
+    use crate::data_types::data_types::DataTypes;
+    use crate::errors::errors::CptError;
+    use crate::nodes::nodes::NodeId;
+    use crate::subtrees::subtrees::SubtreeIndex;
+
+    // Options of the pattern mining.
+    #[derive(Clone, Debug)]
+    pub struct MiningOptions {
+        // Patterns contained in fewer training sequences are left out
+        pub min_support: usize,
+        // Longest pattern mined, in items, nothing is mined when it is 0
+        pub max_length: usize,
+        // Number of patterns returned, the most frequent ones
+        pub top_k: usize,
+    }
+
+    impl Default for MiningOptions {
+        fn default() -> Self {
+            MiningOptions { min_support: 2, max_length: 5, top_k: 10 }
+        }
+    }
+
+    // A pattern with the number of training sequences containing it.
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    pub struct FrequentPattern {
+        pub items: Vec<DataTypes>,
+        pub support: usize,
+    }
+
+    // Options of the sequential rules extraction.
+    #[derive(Clone, Debug)]
+    pub struct RuleOptions {
+        // Rules holding in fewer training sequences are left out
+        pub min_support: usize,
+        pub min_confidence: f32,
+        pub min_lift: f32,
+        // Largest number of items of an antecedent
+        pub max_antecedent: usize,
+    }
+
+    impl Default for RuleOptions {
+        fn default() -> Self {
+            RuleOptions { min_support: 2, min_confidence: 0.5, min_lift: 0.0, max_antecedent: 2 }
+        }
+    }
+
+    // A rule "the sequences holding every antecedent item hold the consequent later".
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    pub struct SequentialRule {
+        // Sorted items, in any order in the sequences
+        pub antecedent: Vec<DataTypes>,
+        pub consequent: DataTypes,
+        // Number of training sequences where the consequent follows all the antecedent items
+        pub support: usize,
+        // Share of the sequences holding the antecedent where the rule holds
+        pub confidence: f32,
+        // Confidence divided by the share of the sequences holding the consequent
+        pub lift: f32,
+    }
+
+    impl FmtDisplay for SequentialRule {
+        fn fmt(&self, f: &mut FmtFormatter<'_>) -> FmtResult {
+            let antecedent: Vec<String> = self.antecedent.iter().map(|item| format!("{:?}", item)).collect();
+            write!(f, "{} => {:?} (support {}, confidence {:.3}, lift {:.3})", antecedent.join(", "), self.consequent, self.support, self.confidence, self.lift)
+        }
+    }
+
+    // Exports the rules as a JSON list.
+    pub fn rules_to_json(rules: &[SequentialRule]) -> Result<String, CptError> {
+        Ok(serde_json::to_string(rules)?)
+    }
+
+    impl CPT<DataTypes> {
+        // Returns the most frequent contiguous patterns of the training sequences.
+        // Patterns are ordered by decreasing support, then by decreasing length.
+        pub fn frequent_ngrams(&self, options: &MiningOptions) -> Vec<FrequentPattern> {
+            if options.max_length == 0 {
+                return vec![];
+            }
+            let subtrees = SubtreeIndex::new(self);
+            let mut patterns = Vec::<FrequentPattern>::new();
+            // The one item patterns occur at the nodes of the inverted index,
+            // longer patterns at the children of the occurrences of their prefix
+            let mut stack: Vec<(Vec<DataTypes>, Vec<NodeId>)> = self.inverted_index.values.iter().copied()
+                .zip(self.inverted_index.node_ids.iter().cloned())
+                .map(|(value, node_ids)| (vec![value], node_ids))
+                .collect();
+            while let Some((items, occurrences)) = stack.pop() {
+                let support = subtrees.support(&subtrees.antichain(occurrences.clone()));
+                if support < options.min_support.max(1) {
+                    continue;
+                }
+                if items.len() < options.max_length {
+                    let mut extensions = HashMap::<DataTypes, Vec<NodeId>>::new();
+                    for &node_id in occurrences.iter() {
+                        for &child_node_id in self.nodes[node_id].children.iter() {
+                            if let Some(child_node_data) = self.nodes[child_node_id].data {
+                                extensions.entry(child_node_data).or_default().push(child_node_id);
+                            }
+                        }
+                    }
+                    stack.extend(extensions.into_iter().map(|(value, child_node_ids)| {
+                        let mut extended_items = items.clone();
+                        extended_items.push(value);
+                        (extended_items, child_node_ids)
+                    }));
+                }
+                patterns.push(FrequentPattern { items, support });
+            }
+            top_patterns(patterns, options.top_k)
+        }
+
+        // Returns the most frequent sequential patterns of the training sequences: items
+        // appearing in this order, not necessarily next to each other.
+        // Patterns are ordered by decreasing support, then by decreasing length.
+        pub fn frequent_subsequences(&self, options: &MiningOptions) -> Vec<FrequentPattern> {
+            if options.max_length == 0 {
+                return vec![];
+            }
+            let subtrees = SubtreeIndex::new(self);
+            let min_support = options.min_support.max(1);
+            // Only the frequent items can extend a frequent pattern
+            let frequent_items: Vec<(DataTypes, &Vec<NodeId>)> = self.inverted_index.values.iter().copied()
+                .zip(self.inverted_index.node_ids.iter())
+                .filter(|(_value, node_ids)| subtrees.support(&subtrees.antichain(node_ids.to_vec())) >= min_support)
+                .collect();
+            let mut patterns = Vec::<FrequentPattern>::new();
+            // A pattern is projected on the first nodes holding its last item, on each path
+            let mut stack: Vec<(Vec<DataTypes>, Vec<NodeId>)> = frequent_items.iter()
+                .map(|&(value, node_ids)| (vec![value], subtrees.antichain(node_ids.to_vec())))
+                .collect();
+            while let Some((items, projection)) = stack.pop() {
+                if items.len() < options.max_length {
+                    for &(value, node_ids) in frequent_items.iter() {
+                        let extended_projection = subtrees.antichain(subtrees.below(&projection, node_ids));
+                        if subtrees.support(&extended_projection) >= min_support {
+                            let mut extended_items = items.clone();
+                            extended_items.push(value);
+                            stack.push((extended_items, extended_projection));
+                        }
+                    }
+                }
+                patterns.push(FrequentPattern { support: subtrees.support(&projection), items });
+            }
+            top_patterns(patterns, options.top_k)
+        }
+
+        // Returns the sequential rules passing the thresholds, by decreasing confidence, then decreasing support.
+        pub fn sequential_rules(&self, options: &RuleOptions) -> Vec<SequentialRule> {
+            let min_support = options.min_support.max(1);
+            let mut last_node_ids: Vec<NodeId> = self.sequences_lookup_table.keys().copied().collect();
+            last_node_ids.sort_unstable();
+            let sequences: Vec<Vec<DataTypes>> = last_node_ids.into_iter().map(|last_node_id| self.get_sequence(last_node_id)).collect();
+
+            // Items held by too few sequences cannot be part of a rule
+            let mut item_supports = HashMap::<DataTypes, usize>::new();
+            for sequence in sequences.iter() {
+                sequence.iter().collect::<HashSet<&DataTypes>>().into_iter().for_each(|&item| *item_supports.entry(item).or_insert(0) += 1);
+            }
+            // First and last position of each frequent item in each sequence
+            let positions: Vec<HashMap<DataTypes, (usize, usize)>> = sequences.iter().map(|sequence| {
+                let mut positions = HashMap::<DataTypes, (usize, usize)>::new();
+                for (position, &item) in sequence.iter().enumerate().filter(|(_position, item)| item_supports[item] >= min_support) {
+                    positions.entry(item).or_insert((position, position)).1 = position;
+                }
+                positions
+            }).collect();
+
+            // The antecedents are grown one item at a time, like in Apriori: a rule is at most as
+            // frequent as its antecedent, so only the antecedents held by min_support sequences are
+            // extended. Each antecedent keeps the sorted indices of the sequences holding it.
+            let mut frequent_items: Vec<DataTypes> = item_supports.iter().filter(|&(_item, &support)| support >= min_support).map(|(&item, _support)| item).collect();
+            frequent_items.sort_unstable();
+            let mut level: Vec<(Vec<DataTypes>, Vec<usize>)> = frequent_items.into_iter().map(|item| {
+                (vec![item], (0..sequences.len()).filter(|&index| positions[index].contains_key(&item)).collect())
+            }).collect();
+            let sequence_count = sequences.len() as f32;
+            let mut rules = Vec::<SequentialRule>::new();
+            for antecedent_size in 1..=options.max_antecedent {
+                for (antecedent, sequence_indices) in level.iter() {
+                    // The rule holds when the consequent occurs after every antecedent item
+                    let mut rule_supports = HashMap::<DataTypes, usize>::new();
+                    for &index in sequence_indices.iter() {
+                        let antecedent_end = antecedent.iter().map(|item| positions[index][item].0).max().unwrap_or(0);
+                        for (&consequent, _) in positions[index].iter().filter(|&(item, &(_first, last))| last > antecedent_end && !antecedent.contains(item)) {
+                            *rule_supports.entry(consequent).or_insert(0) += 1;
+                        }
+                    }
+                    rules.extend(rule_supports.into_iter()
+                        .filter(|&(_, support)| support >= min_support)
+                        .map(|(consequent, support)| {
+                            let confidence = support as f32 / sequence_indices.len() as f32;
+                            let lift = confidence / (item_supports[&consequent] as f32 / sequence_count);
+                            SequentialRule { antecedent: antecedent.clone(), consequent, support, confidence, lift }
+                        })
+                        .filter(|rule| rule.confidence >= options.min_confidence && rule.lift >= options.min_lift));
+                }
+                if antecedent_size == options.max_antecedent {
+                    break;
+                }
+                // Two sorted antecedents sharing all their items but the last one make a larger antecedent
+                let mut next_level = Vec::<(Vec<DataTypes>, Vec<usize>)>::new();
+                for (position, (antecedent, sequence_indices)) in level.iter().enumerate() {
+                    let prefix = &antecedent[..antecedent_size - 1];
+                    for (other_antecedent, other_sequence_indices) in level[position + 1..].iter().take_while(|(other_antecedent, _)| other_antecedent.starts_with(prefix)) {
+                        let shared_indices = intersect_sorted(sequence_indices, other_sequence_indices);
+                        if shared_indices.len() >= min_support {
+                            let mut extended_antecedent = antecedent.clone();
+                            extended_antecedent.push(other_antecedent[antecedent_size - 1]);
+                            next_level.push((extended_antecedent, shared_indices));
+                        }
+                    }
+                }
+                if next_level.is_empty() {
+                    break;
+                }
+                level = next_level;
+            }
+            rules.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| b.support.cmp(&a.support))
+                .then_with(|| a.antecedent.cmp(&b.antecedent))
+                .then_with(|| a.consequent.cmp(&b.consequent)));
+            rules
+        }
+    }
+
+    // The values held by both sorted lists
+    fn intersect_sorted(a: &[usize], b: &[usize]) -> Vec<usize> {
+        let mut shared = Vec::<usize>::with_capacity(a.len().min(b.len()));
+        let (mut a_position, mut b_position) = (0, 0);
+        while a_position < a.len() && b_position < b.len() {
+            match a[a_position].cmp(&b[b_position]) {
+                std::cmp::Ordering::Less => a_position += 1,
+                std::cmp::Ordering::Greater => b_position += 1,
+                std::cmp::Ordering::Equal => {
+                    shared.push(a[a_position]);
+                    a_position += 1;
+                    b_position += 1;
+                }
+            }
+        }
+        shared
+    }
+
+    // The top_k patterns, by decreasing support, then decreasing length, then items
+    fn top_patterns(mut patterns: Vec<FrequentPattern>, top_k: usize) -> Vec<FrequentPattern> {
+        patterns.sort_by(|a, b| b.support.cmp(&a.support)
+            .then_with(|| b.items.len().cmp(&a.items.len()))
+            .then_with(|| a.items.cmp(&b.items)));
+        patterns.truncate(top_k);
+        patterns
+    }
+}
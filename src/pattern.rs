@@ -0,0 +1,279 @@
+pub mod pattern {
+    // Pattern queries over the training sequences of a CPT, like `^ 1 * [4..6] ? $`,
+    // and exact subsequence search.
+    //
+    // The candidate sequences are found with the inverted index: a matching sequence ends
+    // below a node holding a value of every literal and range item of the pattern. The
+    // candidates are then read back through the parent links and matched item by item.
+    use std::collections::{BTreeMap, HashSet};
+    use std::str::FromStr;
+
+    use crate::cpt::cpt::CPT;
+    use crate::data_types::data_types::{DataTypes, SequenceAttributes};
+    use crate::errors::errors::CptError;
+    use crate::nodes::nodes::NodeId;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum PatternItem {
+        // `3`, or `'a'` for byte sequences
+        Literal(DataTypes),
+        // `[4..6]`, both bounds included
+        Range(DataTypes, DataTypes),
+        // `?`, any single item
+        Any,
+        // `*`, any run of items, possibly empty
+        AnyRun,
+    }
+
+    impl PatternItem {
+        fn matches(self, value: DataTypes) -> bool {
+            match self {
+                PatternItem::Literal(literal) => value == literal,
+                PatternItem::Range(low, high) => low <= value && value <= high,
+                PatternItem::Any | PatternItem::AnyRun => true,
+            }
+        }
+
+        // The lowest and highest values matched, None for the wildcards
+        fn bounds(self) -> Option<(DataTypes, DataTypes)> {
+            match self {
+                PatternItem::Literal(literal) => Some((literal, literal)),
+                PatternItem::Range(low, high) => Some((low, high)),
+                PatternItem::Any | PatternItem::AnyRun => None,
+            }
+        }
+    }
+
+    // A pattern of items, optionally anchored at the start (`^`) or the end (`$`) of the sequences.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Pattern {
+        pub items: Vec<PatternItem>,
+        pub anchored_start: bool,
+        pub anchored_end: bool,
+    }
+
+    // A training sequence matching a pattern.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct PatternMatch {
+        // The last node of the sequence, its key in the sequences lookup table
+        pub last_node_id: NodeId,
+        pub sequence: Vec<DataTypes>,
+        pub attributes: Vec<SequenceAttributes>,
+        // Position in the sequence of the item matched by each pattern item, `*` excepted
+        pub positions: Vec<usize>,
+    }
+
+    // A training sequence containing a subsequence.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct SubsequenceOccurrences {
+        // The last node of the sequence, its key in the sequences lookup table
+        pub last_node_id: NodeId,
+        pub attributes: Vec<SequenceAttributes>,
+        // Start positions of the subsequence in the sequence, in increasing order
+        pub positions: Vec<usize>,
+    }
+
+    impl Pattern {
+        // Parses a pattern of items separated by spaces: values, `'c'` bytes, `[low..high]`
+        // ranges, `?` and `*`, optionally starting with `^` and ending with `$`.
+        pub fn parse(pattern: &str) -> Result<Pattern, CptError> {
+            let mut pattern = pattern.trim();
+            let anchored_start = pattern.starts_with('^');
+            if anchored_start {
+                pattern = &pattern[1..];
+            }
+            let anchored_end = pattern.ends_with('$');
+            if anchored_end {
+                pattern = &pattern[..pattern.len() - 1];
+            }
+            let items = pattern.split_whitespace().map(parse_item).collect::<Result<Vec<PatternItem>, CptError>>()?;
+            if items.is_empty() {
+                return Err(CptError::InvalidPattern("The pattern has no item".to_string()));
+            }
+            Ok(Pattern { items, anchored_start, anchored_end })
+        }
+
+        // Matches the pattern against a sequence, and returns the positions of the items
+        // matched by the pattern items, `*` excepted. The leftmost match is returned,
+        // with the shortest runs.
+        pub fn find(&self, sequence: &[DataTypes]) -> Option<Vec<usize>> {
+            let last_start = if self.anchored_start { 0 } else { sequence.len() };
+            let mut positions = Vec::<usize>::new();
+            // Whether the pattern items from an index can no longer match from a position.
+            // Each pair is tried at most once, whatever the number of `*`.
+            let mut failed = vec![false; (self.items.len() + 1) * (sequence.len() + 1)];
+            (0..=last_start).find(|&start| self.match_from(0, sequence, start, &mut positions, &mut failed))
+                .map(|_start| positions)
+        }
+
+        fn match_from(&self, item_index: usize, sequence: &[DataTypes], position: usize, positions: &mut Vec<usize>, failed: &mut [bool]) -> bool {
+            let state = item_index * (sequence.len() + 1) + position;
+            if failed[state] {
+                return false;
+            }
+            let matched = match self.items.get(item_index) {
+                None => !self.anchored_end || position == sequence.len(),
+                Some(PatternItem::AnyRun) => {
+                    (position..=sequence.len()).any(|next_position| self.match_from(item_index + 1, sequence, next_position, positions, failed))
+                },
+                Some(&item) => {
+                    position < sequence.len() && item.matches(sequence[position]) && {
+                        positions.push(position);
+                        let matched = self.match_from(item_index + 1, sequence, position + 1, positions, failed);
+                        if !matched {
+                            positions.pop();
+                        }
+                        matched
+                    }
+                }
+            };
+            failed[state] = !matched;
+            matched
+        }
+    }
+
+    impl FromStr for Pattern {
+        type Err = CptError;
+
+        fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+            Pattern::parse(pattern)
+        }
+    }
+
+    fn parse_item(token: &str) -> Result<PatternItem, CptError> {
+        match token {
+            "*" => Ok(PatternItem::AnyRun),
+            "?" => Ok(PatternItem::Any),
+            _ if token.starts_with('[') && token.ends_with(']') && token.len() > 1 => {
+                let (low, high) = token[1..token.len() - 1].split_once("..")
+                    .ok_or_else(|| CptError::InvalidPattern(format!("Expected a range like [4..6], got {:?}", token)))?;
+                let (low, high) = (parse_value(low)?, parse_value(high)?);
+                if low > high {
+                    return Err(CptError::InvalidPattern(format!("Empty range {:?}", token)));
+                }
+                Ok(PatternItem::Range(low, high))
+            },
+            _ => Ok(PatternItem::Literal(parse_value(token)?))
+        }
+    }
+
+    fn parse_value(token: &str) -> Result<DataTypes, CptError> {
+        let chars: Vec<char> = token.chars().collect();
+        match chars.as_slice() {
+            ['\'', byte, '\''] if byte.is_ascii() => Ok(DataTypes::U8(*byte as u8)),
+            _ => token.parse::<usize>().map(DataTypes::Integer)
+                .map_err(|_| CptError::InvalidPattern(format!("Invalid item {:?}", token)))
+        }
+    }
+
+    impl CPT<DataTypes> {
+        // Returns the training sequences matching the pattern, ordered by last node id.
+        pub fn query(&self, pattern: &Pattern) -> Vec<PatternMatch> {
+            self.pattern_candidates(pattern).into_iter().filter_map(|last_node_id| {
+                let sequence = self.get_sequence(last_node_id);
+                pattern.find(&sequence).map(|positions| PatternMatch {
+                    last_node_id,
+                    attributes: self.sequences_lookup_table.get(&last_node_id).cloned().unwrap_or_default(),
+                    sequence,
+                    positions,
+                })
+            }).collect()
+        }
+
+        // Returns the training sequences containing the contiguous subsequence, ordered by last node id.
+        // Identical training sequences are stored once, and are returned once with all their attributes.
+        pub fn find_subsequence(&self, subsequence: &[DataTypes]) -> Vec<SubsequenceOccurrences> {
+            let mut occurrences = BTreeMap::<NodeId, Vec<usize>>::new();
+            for (start_node_id, end_node_id) in self.subsequence_nodes(subsequence) {
+                // The depth of the first node is its position in every sequence going through it
+                let position = self.node_depth(start_node_id) - 1;
+                for last_node_id in self.sequences_ending_below(vec![end_node_id]) {
+                    occurrences.entry(last_node_id).or_default().push(position);
+                }
+            }
+            occurrences.into_iter().map(|(last_node_id, mut positions)| {
+                positions.sort_unstable();
+                SubsequenceOccurrences {
+                    last_node_id,
+                    attributes: self.sequences_lookup_table.get(&last_node_id).cloned().unwrap_or_default(),
+                    positions,
+                }
+            }).collect()
+        }
+
+        // Returns the number of distinct training sequences containing the contiguous subsequence.
+        // Each stored sequence counts once, however many times it holds the subsequence or was
+        // added: identical sequences are stored once. `find_subsequence` gives the positions.
+        pub fn count_sequences_containing(&self, subsequence: &[DataTypes]) -> usize {
+            let end_node_ids = self.subsequence_nodes(subsequence).into_iter().map(|(_start_node_id, end_node_id)| end_node_id).collect();
+            self.sequences_ending_below(end_node_ids).len()
+        }
+
+        // The first and last nodes of each path of the tree holding the subsequence
+        fn subsequence_nodes(&self, subsequence: &[DataTypes]) -> Vec<(NodeId, NodeId)> {
+            let (first_item, next_items) = match subsequence.split_first() {
+                Some(split) => split,
+                None => return vec![]
+            };
+            self.inverted_index.get_value_ids(*first_item).into_iter().flatten().filter_map(|&start_node_id| {
+                next_items.iter()
+                    .try_fold(start_node_id, |node_id, &item| self.child_exists(item, node_id))
+                    .map(|end_node_id| (start_node_id, end_node_id))
+            }).collect()
+        }
+
+        // Number of nodes from the root, excluded, to the node
+        fn node_depth(&self, node_id: NodeId) -> usize {
+            std::iter::successors(Some(node_id), |&id| self.nodes.get(id).and_then(|node| node.parent)).count() - 1
+        }
+
+        // The last nodes of the sequences going through any of the nodes
+        fn sequences_ending_below(&self, node_ids: Vec<NodeId>) -> HashSet<NodeId> {
+            let mut last_node_ids = HashSet::<NodeId>::new();
+            let mut visited = HashSet::<NodeId>::new();
+            let mut stack = node_ids;
+            while let Some(node_id) = stack.pop() {
+                if !visited.insert(node_id) {
+                    continue;
+                }
+                if self.sequences_lookup_table.contains_key(&node_id) {
+                    last_node_ids.insert(node_id);
+                }
+                if let Some(node) = self.nodes.get(node_id) {
+                    stack.extend(node.children.iter().copied());
+                }
+            }
+            last_node_ids
+        }
+
+        // The last nodes of the sequences holding a value of every literal and range item,
+        // every sequence when the pattern only has wildcards
+        fn pattern_candidates(&self, pattern: &Pattern) -> Vec<NodeId> {
+            let mut candidates: Option<HashSet<NodeId>> = None;
+            for (low, high) in pattern.items.iter().filter_map(|item| item.bounds()) {
+                // An inverted range, built through the public fields, matches no value
+                if low > high {
+                    return vec![];
+                }
+                let values = &self.inverted_index.values;
+                let positions = values.partition_point(|&value| value < low)..values.partition_point(|&value| value <= high);
+                // The sequences going through the nodes holding one of the values
+                let last_node_ids = self.sequences_ending_below(self.inverted_index.node_ids[positions].iter().flatten().copied().collect());
+                let item_candidates = match candidates {
+                    Some(candidates) => candidates.intersection(&last_node_ids).copied().collect(),
+                    None => last_node_ids
+                };
+                if item_candidates.is_empty() {
+                    return vec![];
+                }
+                candidates = Some(item_candidates);
+            }
+            let mut candidates: Vec<NodeId> = match candidates {
+                Some(candidates) => candidates.into_iter().collect(),
+                None => self.sequences_lookup_table.keys().copied().collect()
+            };
+            candidates.sort_unstable();
+            candidates
+        }
+    }
+}
@@ -0,0 +1,71 @@
+pub mod persistence {
+    use std::fs::File;
+    use std::io::{BufReader, BufWriter, Read, Write};
+    use std::path::Path;
+    use serde::{Serialize, Deserialize};
+
+    use crate::binary::binary::MAGIC;
+    use crate::cpt::cpt::CPT;
+    use crate::data_types::data_types::DataTypes;
+    use crate::errors::errors::CptError;
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ModelFormat {
+        // Output of to_json, readable but large
+        Json,
+        // Output of write_binary
+        Binary
+    }
+
+    impl ModelFormat {
+        pub fn from_name(name: &str) -> Option<ModelFormat> {
+            match name {
+                "json" => Some(ModelFormat::Json),
+                "binary" => Some(ModelFormat::Binary),
+                _ => None
+            }
+        }
+    }
+
+    impl CPT<DataTypes> {
+        // Loads a model in any supported format: binary models are recognized by
+        // their header, anything else is read as JSON. The model is validated,
+        // and its inverted index rebuilt if needed.
+        pub fn load<R: Read>(mut reader: R) -> Result<CPT<DataTypes>, CptError> {
+            let mut header = Vec::<u8>::with_capacity(MAGIC.len());
+            (&mut reader).take(MAGIC.len() as u64).read_to_end(&mut header)?;
+            let mut reader = header.as_slice().chain(reader);
+            let mut cpt = if header == MAGIC {
+                CPT::read_binary(reader)?
+            } else {
+                let mut json = String::new();
+                reader.read_to_string(&mut json)?;
+                serde_json::from_str(&json)?
+            };
+            cpt.validate()?;
+            Ok(cpt)
+        }
+
+        // Loads a model file in any supported format, see `load`.
+        pub fn load_file<P: AsRef<Path>>(path: P) -> Result<CPT<DataTypes>, CptError> {
+            CPT::load(BufReader::new(File::open(path)?))
+        }
+
+        // Writes the model in the given format.
+        pub fn save<W: Write>(&self, mut writer: W, format: ModelFormat) -> Result<(), CptError> {
+            match format {
+                ModelFormat::Json => {
+                    serde_json::to_writer(&mut writer, self)?;
+                    writer.flush()?;
+                },
+                ModelFormat::Binary => self.write_binary(writer)?
+            }
+            Ok(())
+        }
+
+        // Writes the model to a file in the given format.
+        pub fn save_file<P: AsRef<Path>>(&self, path: P, format: ModelFormat) -> Result<(), CptError> {
+            self.save(BufWriter::new(File::create(path)?), format)
+        }
+    }
+}
@@ -0,0 +1,419 @@
+pub mod predictors {
+    use std::collections::HashMap;
+    use std::cmp::Ordering;
+    use serde::{Serialize, Deserialize};
+
+    use crate::cpt::cpt::CPT;
+    use crate::data_types::data_types::DataTypes;
+    use crate::errors::errors::CptError;
+    use crate::evaluation::evaluation;
+
+    // A predicted next item and its score: higher scores are better.
+    pub type Prediction = (DataTypes, f32);
+
+    // Common interface of every sequence prediction model of the crate,
+    // so that the CPT and the baselines can be trained and evaluated the same way.
+    pub trait SequencePredictor {
+        // Short name of the model, used in evaluation reports and on the command line.
+        fn name(&self) -> &str;
+
+        // Adds a training sequence to the model.
+        fn train(&mut self, sequence: &[DataTypes]);
+
+        // Returns the candidate next items of `sequence`, best first.
+        // Only the last `prefix_length` items of the sequence are used as context.
+        fn predict_next(&self, sequence: &[DataTypes], prefix_length: usize) -> Vec<Prediction>;
+
+        // Same as `predict_next`, but returns the errors of the models that can fail instead of panicking.
+        fn try_predict_next(&self, sequence: &[DataTypes], prefix_length: usize) -> Result<Vec<Prediction>, CptError> {
+            Ok(self.predict_next(sequence, prefix_length))
+        }
+    }
+
+    // Names accepted by `predictor_from_name`.
+    pub const PREDICTOR_NAMES: [&str; 7] = ["cpt", "markov", "akom", "ppm", "dg", "popularity", "hybrid"];
+
+    // Builds an untrained predictor from its name, with default settings.
+    pub fn predictor_from_name(name: &str) -> Option<Box<dyn SequencePredictor>> {
+        match name {
+            "cpt" => Some(Box::new(CPT::new())),
+            "markov" => Some(Box::new(MarkovPredictor::new())),
+            "akom" => Some(Box::new(AllKOrderMarkov::new(5))),
+            "ppm" => Some(Box::new(PPMPredictor::new(3))),
+            "dg" => Some(Box::new(DependencyGraph::new(4))),
+            "popularity" => Some(Box::new(PopularityPredictor::new())),
+            "hybrid" => Some(Box::new(HybridPredictor::new(Box::new(MarkovPredictor::new())))),
+            _ => None
+        }
+    }
+
+    fn sort_predictions(predictions: &mut [Prediction]) {
+        // Best score first, ties are broken on the item so that results are stable
+        predictions.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal).then(a.0.cmp(&b.0))
+        });
+    }
+
+    fn normalized_counts(counts: &HashMap<DataTypes, usize>) -> Vec<Prediction> {
+        let total: usize = counts.values().sum();
+        let mut predictions: Vec<Prediction> = counts.iter()
+            .map(|(&item, &count)| (item, count as f32 / total as f32))
+            .collect();
+        sort_predictions(&mut predictions);
+        predictions
+    }
+
+    impl SequencePredictor for CPT<DataTypes> {
+        fn name(&self) -> &str {
+            "cpt"
+        }
+
+        fn train(&mut self, sequence: &[DataTypes]) {
+            self.add_sequence_to_root(sequence.to_vec(), None);
+        }
+
+        fn predict_next(&self, sequence: &[DataTypes], prefix_length: usize) -> Vec<Prediction> {
+            self.try_predict_next(sequence, prefix_length).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        fn try_predict_next(&self, sequence: &[DataTypes], prefix_length: usize) -> Result<Vec<Prediction>, CptError> {
+            // The CPT ranks its items on the support first, which is used as the score
+            Ok(self.try_predict(sequence, prefix_length.min(sequence.len()))?.into_iter()
+                .map(|(item, support, _confidence)| (item, support as f32))
+                .collect())
+        }
+    }
+
+    #[derive(Debug, Default)]
+    pub struct MarkovPredictor {
+        // First order Markov chain: counts of the items following each item
+        transitions: HashMap<DataTypes, HashMap<DataTypes, usize>>
+    }
+
+    impl MarkovPredictor {
+        pub fn new() -> MarkovPredictor {
+            Self::default()
+        }
+    }
+
+    impl SequencePredictor for MarkovPredictor {
+        fn name(&self) -> &str {
+            "markov"
+        }
+
+        fn train(&mut self, sequence: &[DataTypes]) {
+            sequence.windows(2).for_each(|pair| {
+                *self.transitions.entry(pair[0]).or_default().entry(pair[1]).or_insert(0) += 1;
+            });
+        }
+
+        fn predict_next(&self, sequence: &[DataTypes], prefix_length: usize) -> Vec<Prediction> {
+            if prefix_length == 0 {
+                return vec![];
+            }
+            match sequence.last().and_then(|last| self.transitions.get(last)) {
+                Some(counts) => normalized_counts(counts),
+                None => vec![]
+            }
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct ContextCounts {
+        // Counts of the items following each context, for contexts of length 0 to max_order
+        counts: HashMap<Vec<DataTypes>, HashMap<DataTypes, usize>>
+    }
+
+    impl ContextCounts {
+        fn train(&mut self, sequence: &[DataTypes], min_order: usize, max_order: usize) {
+            for (position, &item) in sequence.iter().enumerate() {
+                for order in min_order..=max_order.min(position) {
+                    let context = sequence[(position - order)..position].to_vec();
+                    *self.counts.entry(context).or_default().entry(item).or_insert(0) += 1;
+                }
+            }
+        }
+
+        fn get(&self, context: &[DataTypes]) -> Option<&HashMap<DataTypes, usize>> {
+            self.counts.get(context)
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct AllKOrderMarkov {
+        // Markov chains of order 1 to max_order, the longest context seen in training is used
+        max_order: usize,
+        contexts: ContextCounts
+    }
+
+    impl AllKOrderMarkov {
+        pub fn new(max_order: usize) -> AllKOrderMarkov {
+            AllKOrderMarkov { max_order, contexts: ContextCounts::default() }
+        }
+    }
+
+    impl SequencePredictor for AllKOrderMarkov {
+        fn name(&self) -> &str {
+            "akom"
+        }
+
+        fn train(&mut self, sequence: &[DataTypes]) {
+            self.contexts.train(sequence, 1, self.max_order);
+        }
+
+        fn predict_next(&self, sequence: &[DataTypes], prefix_length: usize) -> Vec<Prediction> {
+            let longest_order = self.max_order.min(prefix_length).min(sequence.len());
+            (1..=longest_order).rev()
+                .find_map(|order| self.contexts.get(&sequence[(sequence.len() - order)..]))
+                .map(normalized_counts)
+                .unwrap_or_default()
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct PPMPredictor {
+        // Prediction by Partial Matching (method C escapes): the predictions of each context
+        // length are blended, from the longest context down to the item frequencies
+        max_order: usize,
+        contexts: ContextCounts
+    }
+
+    impl PPMPredictor {
+        pub fn new(max_order: usize) -> PPMPredictor {
+            PPMPredictor { max_order, contexts: ContextCounts::default() }
+        }
+    }
+
+    impl SequencePredictor for PPMPredictor {
+        fn name(&self) -> &str {
+            "ppm"
+        }
+
+        fn train(&mut self, sequence: &[DataTypes]) {
+            self.contexts.train(sequence, 0, self.max_order);
+        }
+
+        fn predict_next(&self, sequence: &[DataTypes], prefix_length: usize) -> Vec<Prediction> {
+            let longest_order = self.max_order.min(prefix_length).min(sequence.len());
+            let mut scores = HashMap::<DataTypes, f32>::new();
+            // Probability mass left after escaping from the longer contexts
+            let mut escape_mass = 1.0;
+            for order in (0..=longest_order).rev() {
+                if let Some(counts) = self.contexts.get(&sequence[(sequence.len() - order)..]) {
+                    let total: usize = counts.values().sum();
+                    let denominator = (total + counts.len()) as f32;
+                    counts.iter().for_each(|(&item, &count)| {
+                        *scores.entry(item).or_insert(0.0) += escape_mass * count as f32 / denominator;
+                    });
+                    escape_mass *= counts.len() as f32 / denominator;
+                }
+            }
+            let mut predictions: Vec<Prediction> = scores.into_iter().collect();
+            sort_predictions(&mut predictions);
+            predictions
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct DependencyGraph {
+        // Each item points to the items appearing at most `lookahead` positions after it
+        lookahead: usize,
+        occurrences: HashMap<DataTypes, usize>,
+        arcs: HashMap<DataTypes, HashMap<DataTypes, usize>>
+    }
+
+    impl DependencyGraph {
+        pub fn new(lookahead: usize) -> DependencyGraph {
+            DependencyGraph { lookahead, occurrences: HashMap::new(), arcs: HashMap::new() }
+        }
+    }
+
+    impl SequencePredictor for DependencyGraph {
+        fn name(&self) -> &str {
+            "dg"
+        }
+
+        fn train(&mut self, sequence: &[DataTypes]) {
+            for (position, &item) in sequence.iter().enumerate() {
+                *self.occurrences.entry(item).or_insert(0) += 1;
+                let window_end = (position + 1 + self.lookahead).min(sequence.len());
+                sequence[(position + 1)..window_end].iter().for_each(|&next_item| {
+                    *self.arcs.entry(item).or_default().entry(next_item).or_insert(0) += 1;
+                });
+            }
+        }
+
+        fn predict_next(&self, sequence: &[DataTypes], prefix_length: usize) -> Vec<Prediction> {
+            if prefix_length == 0 {
+                return vec![];
+            }
+            // The score of an arc is the probability of reaching its item from the last item
+            match sequence.last().and_then(|last| self.arcs.get(last).map(|arcs| (last, arcs))) {
+                Some((last, arcs)) => {
+                    let occurrences = self.occurrences[last] as f32;
+                    let mut predictions: Vec<Prediction> = arcs.iter()
+                        .map(|(&item, &count)| (item, count as f32 / occurrences))
+                        .collect();
+                    sort_predictions(&mut predictions);
+                    predictions
+                }
+                None => vec![]
+            }
+        }
+    }
+
+    #[derive(Debug, Default)]
+    pub struct PopularityPredictor {
+        // Always predicts the most frequent items of the training set
+        frequencies: HashMap<DataTypes, usize>
+    }
+
+    impl PopularityPredictor {
+        pub fn new() -> PopularityPredictor {
+            Self::default()
+        }
+    }
+
+    impl SequencePredictor for PopularityPredictor {
+        fn name(&self) -> &str {
+            "popularity"
+        }
+
+        fn train(&mut self, sequence: &[DataTypes]) {
+            sequence.iter().for_each(|&item| *self.frequencies.entry(item).or_insert(0) += 1);
+        }
+
+        fn predict_next(&self, _sequence: &[DataTypes], _prefix_length: usize) -> Vec<Prediction> {
+            if self.frequencies.is_empty() {
+                return vec![];
+            }
+            normalized_counts(&self.frequencies)
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum PredictionSource {
+        // Only the CPT predicted the item
+        Primary,
+        // Only the fallback model predicted the item
+        Fallback,
+        // Both models predicted the item, and their scores were blended
+        Blended
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+    pub struct HybridPrediction {
+        pub item: DataTypes,
+        pub score: f32,
+        pub source: PredictionSource
+    }
+
+    pub struct HybridPredictor {
+        // Ensemble asking the CPT first, and completing its answer with a fallback model
+        // when the CPT has nothing or little to say about a query
+        cpt: CPT<DataTypes>,
+        fallback: Box<dyn SequencePredictor>,
+        // The fallback model is used when the best CPT support is below this value
+        pub min_support: usize,
+        // Weight of the CPT scores when blending, the fallback scores get the rest
+        pub cpt_weight: f32
+    }
+
+    impl HybridPredictor {
+        pub fn new(fallback: Box<dyn SequencePredictor>) -> HybridPredictor {
+            HybridPredictor { cpt: CPT::new(), fallback, min_support: 2, cpt_weight: 0.7 }
+        }
+
+        pub fn cpt(&self) -> &CPT<DataTypes> {
+            &self.cpt
+        }
+
+        fn scaled(predictions: Vec<Prediction>) -> HashMap<DataTypes, f32> {
+            // Scale the scores of a model to [0, 1] so that different models can be blended
+            let max_score = predictions.iter().map(|prediction| prediction.1).fold(0.0, f32::max);
+            predictions.into_iter()
+                .map(|(item, score)| (item, if max_score > 0.0 { score / max_score } else { 0.0 }))
+                .collect()
+        }
+
+        // Predicts the next items with the CPT, then falls back to or blends with
+        // the fallback model if the CPT result is empty or its support is too low.
+        // Scores are in [0, 1] whatever the model used, and each prediction records the model it came from.
+        pub fn predict_hybrid(&self, sequence: &[DataTypes], prefix_length: usize) -> Vec<HybridPrediction> {
+            self.try_predict_hybrid(sequence, prefix_length).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        pub fn try_predict_hybrid(&self, sequence: &[DataTypes], prefix_length: usize) -> Result<Vec<HybridPrediction>, CptError> {
+            let cpt_predictions = self.cpt.try_predict_next(sequence, prefix_length)?;
+            let best_support = cpt_predictions.first().map(|prediction| prediction.1).unwrap_or(0.0);
+
+            // Every branch scales its scores to [0, 1], so that the scores of different queries can be compared
+            let mut predictions: Vec<HybridPrediction> = if best_support >= self.min_support as f32 {
+                HybridPredictor::scaled(cpt_predictions).into_iter()
+                    .map(|(item, score)| HybridPrediction { item, score, source: PredictionSource::Primary })
+                    .collect()
+            } else if cpt_predictions.is_empty() {
+                HybridPredictor::scaled(self.fallback.try_predict_next(sequence, prefix_length)?).into_iter()
+                    .map(|(item, score)| HybridPrediction { item, score, source: PredictionSource::Fallback })
+                    .collect()
+            } else {
+                let cpt_scores = HybridPredictor::scaled(cpt_predictions);
+                let mut fallback_scores = HybridPredictor::scaled(self.fallback.try_predict_next(sequence, prefix_length)?);
+                let mut blended: Vec<HybridPrediction> = cpt_scores.into_iter().map(|(item, cpt_score)| {
+                    match fallback_scores.remove(&item) {
+                        Some(fallback_score) => HybridPrediction {
+                            item,
+                            score: self.cpt_weight * cpt_score + (1.0 - self.cpt_weight) * fallback_score,
+                            source: PredictionSource::Blended
+                        },
+                        None => HybridPrediction { item, score: self.cpt_weight * cpt_score, source: PredictionSource::Primary }
+                    }
+                }).collect();
+                blended.extend(fallback_scores.into_iter().map(|(item, fallback_score)| {
+                    HybridPrediction { item, score: (1.0 - self.cpt_weight) * fallback_score, source: PredictionSource::Fallback }
+                }));
+                blended
+            };
+            predictions.sort_by(|a, b| {
+                b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal).then(a.item.cmp(&b.item))
+            });
+            Ok(predictions)
+        }
+
+        // Learns the CPT weight on a validation set: every weight from 0 to 1
+        // by steps of 0.1 is evaluated, and the most accurate one is kept.
+        pub fn fit_weights(&mut self, validation: &[Vec<DataTypes>], prefix_length: usize, top_k: usize) -> Result<f32, CptError> {
+            let mut best = (self.cpt_weight, -1.0);
+            for step in 0..=10 {
+                self.cpt_weight = step as f32 / 10.0;
+                let accuracy = evaluation::evaluate(self, validation, prefix_length, top_k)?.accuracy;
+                if accuracy > best.1 {
+                    best = (self.cpt_weight, accuracy);
+                }
+            }
+            self.cpt_weight = best.0;
+            Ok(self.cpt_weight)
+        }
+    }
+
+    impl SequencePredictor for HybridPredictor {
+        fn name(&self) -> &str {
+            "hybrid"
+        }
+
+        fn train(&mut self, sequence: &[DataTypes]) {
+            self.cpt.train(sequence);
+            self.fallback.train(sequence);
+        }
+
+        fn predict_next(&self, sequence: &[DataTypes], prefix_length: usize) -> Vec<Prediction> {
+            self.try_predict_next(sequence, prefix_length).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        fn try_predict_next(&self, sequence: &[DataTypes], prefix_length: usize) -> Result<Vec<Prediction>, CptError> {
+            Ok(self.try_predict_hybrid(sequence, prefix_length)?.into_iter()
+                .map(|prediction| (prediction.item, prediction.score))
+                .collect())
+        }
+    }
+}
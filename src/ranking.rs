@@ -0,0 +1,96 @@
+pub mod ranking {
+    // Orders the sequences matched by match_sequence when several metrics are used.
+    // Like match_sequence, the best matches are sorted last.
+    use std::cell::RefCell;
+    use std::cmp::Ordering;
+
+    use crate::errors::errors::{CptError, keep_first_error};
+    use crate::nodes::nodes::NodeId;
+
+    // A match with its scores, the same shape for the built-in and the user defined metrics
+    type RankedMatch<N, S> = (Vec<(NodeId, N)>, S);
+
+    // How the matches are ranked from their score for each metric. Whatever the policy,
+    // ties are broken on the node ids of the matches, lower ids ranking higher, so that
+    // the same query always returns the same order.
+    #[derive(Clone, Debug, Default, PartialEq)]
+    pub enum RankingPolicy {
+        // By the first metric, then by the next ones for matches with equal scores
+        #[default]
+        Lexicographic,
+        // By the weighted sum of the scores, each normalized between 0 for the worst
+        // and 1 for the best score among the matches. One weight per metric
+        WeightedSum(Vec<f32>),
+        // Only the matches that no other match beats on every metric, lexicographically ranked.
+        // Every match is compared with every other one before `max_results` applies, a cost quadratic
+        // in the number of matches: bound them with `beam_width` or `score_threshold` on large trees
+        ParetoFront,
+    }
+
+    // Ranks `matches`, the best last. `compare(a, b, metric)` is Greater when `a` scores
+    // better than `b` on a metric, `utility(a, metric)` is a score that is higher when better.
+    // The first error of either function is returned.
+    pub(crate) fn rank<N, S>(
+        mut matches: Vec<RankedMatch<N, S>>,
+        policy: &RankingPolicy,
+        metric_count: usize,
+        compare: impl Fn(&S, &S, usize) -> Result<Ordering, CptError>,
+        utility: impl Fn(&S, usize) -> Result<f32, CptError>,
+    ) -> Result<Vec<RankedMatch<N, S>>, CptError> {
+        let tie_break = |a: &[(NodeId, N)], b: &[(NodeId, N)]| {
+            b.iter().map(|node| node.0).cmp(a.iter().map(|node| node.0))
+        };
+        // The sorts cannot fail, the first comparison error is kept and returned after them
+        let compare_error = RefCell::new(None);
+        let compare = |a: &S, b: &S, metric: usize| keep_first_error(&compare_error, compare(a, b, metric), || Ordering::Equal);
+        let lexicographic = |a: &S, b: &S| {
+            (0..metric_count).map(|metric| compare(a, b, metric))
+                .find(|&ordering| ordering != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        };
+
+        match policy {
+            RankingPolicy::Lexicographic => {
+                matches.sort_by(|a, b| lexicographic(&a.1, &b.1).then_with(|| tie_break(&a.0, &b.0)));
+            },
+            RankingPolicy::WeightedSum(weights) => {
+                if weights.len() != metric_count {
+                    return Err(CptError::InvalidRankingPolicy(format!("{} weights given for {} metrics", weights.len(), metric_count)));
+                }
+                let utilities = matches.iter()
+                    .map(|matched| (0..metric_count).map(|metric| utility(&matched.1, metric)).collect::<Result<Vec<f32>, CptError>>())
+                    .collect::<Result<Vec<Vec<f32>>, CptError>>()?;
+                // Bounds of each metric among the matches, to normalize the scores
+                let bounds: Vec<(f32, f32)> = (0..metric_count).map(|metric| {
+                    utilities.iter().map(|match_utilities| match_utilities[metric])
+                        .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), value| (low.min(value), high.max(value)))
+                }).collect();
+                let weighted_sum = |match_utilities: &[f32]| -> f32 {
+                    weights.iter().zip(bounds.iter()).zip(match_utilities.iter()).map(|((weight, &(low, high)), &value)| {
+                        let normalized = if high > low { (value - low) / (high - low) } else { 1.0 };
+                        weight * normalized
+                    }).sum()
+                };
+                let mut weighted: Vec<(f32, RankedMatch<N, S>)> = matches.into_iter().zip(utilities.iter()).map(|(matched, match_utilities)| (weighted_sum(match_utilities), matched)).collect();
+                weighted.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal).then_with(|| tie_break(&(a.1).0, &(b.1).0)));
+                matches = weighted.into_iter().map(|(_sum, matched)| matched).collect();
+            },
+            RankingPolicy::ParetoFront => {
+                let dominates = |a: &S, b: &S| {
+                    let orderings: Vec<Ordering> = (0..metric_count).map(|metric| compare(a, b, metric)).collect();
+                    orderings.iter().all(|&ordering| ordering != Ordering::Less) && orderings.contains(&Ordering::Greater)
+                };
+                let front: Vec<bool> = matches.iter().map(|matched| {
+                    !matches.iter().any(|other| dominates(&other.1, &matched.1))
+                }).collect();
+                let mut front_iter = front.into_iter();
+                matches.retain(|_| front_iter.next().unwrap_or(false));
+                matches.sort_by(|a, b| lexicographic(&a.1, &b.1).then_with(|| tie_break(&a.0, &b.0)));
+            },
+        }
+        match compare_error.into_inner() {
+            Some(e) => Err(e),
+            None => Ok(matches)
+        }
+    }
+}
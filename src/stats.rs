@@ -0,0 +1,100 @@
+pub mod stats {
+    // Statistics of a trained tree and of the training sequences it holds.
+    // Identical training sequences are stored once, they are counted once,
+    // except in the number of items inserted.
+    use std::collections::BTreeMap;
+    use serde::Serialize;
+
+    use crate::compact::compact::MemoryUsage;
+    use crate::cpt::cpt::CPT;
+    use crate::data_types::data_types::DataTypes;
+    use crate::subtrees::subtrees::SubtreeIndex;
+    use crate::nodes::nodes::NodeId;
+
+    // Report of `CPT::stats`. Histograms map a value to its number of occurrences.
+    #[derive(Serialize, Debug, Clone)]
+    pub struct CptStats {
+        // Nodes of the tree, the root included
+        pub node_count: usize,
+        pub sequence_count: usize,
+        pub max_depth: usize,
+        // Number of nodes at each depth, the root excluded
+        pub depth_histogram: BTreeMap<usize, usize>,
+        // Number of sequences of each length
+        pub length_histogram: BTreeMap<usize, usize>,
+        // Number of nodes with each number of children, the root and the leaves included
+        pub branching_histogram: BTreeMap<usize, usize>,
+        // Mean number of children of the nodes having children
+        pub mean_branching: f32,
+        // Number of distinct items
+        pub vocabulary_size: usize,
+        // Occurrences of each item in the training sequences, the most frequent first
+        pub item_frequencies: Vec<(DataTypes, usize)>,
+        // Items added to the tree, see `CPT::items_inserted`. None for loaded models
+        pub items_inserted: Option<usize>,
+        // Items added for each node created, the root excluded. None when the items added are unknown
+        pub compression_ratio: Option<f32>,
+        // Node ids referenced by the inverted index, which has one entry per item of the vocabulary
+        pub index_node_ids: usize,
+        pub memory: MemoryUsage,
+    }
+
+    impl CptStats {
+        pub fn to_json(&self) -> String {
+            serde_json::to_string(self).unwrap()
+        }
+
+        pub fn to_json_pretty(&self) -> String {
+            serde_json::to_string_pretty(self).unwrap()
+        }
+    }
+
+    impl CPT<DataTypes> {
+        // Computes the statistics of the tree and of its training sequences.
+        pub fn stats(&self) -> CptStats {
+            let subtrees = SubtreeIndex::new(self);
+            let mut depths = vec![0; self.nodes.len()];
+            let mut depth_histogram = BTreeMap::<usize, usize>::new();
+            let mut branching_histogram = BTreeMap::<usize, usize>::new();
+            let mut item_counts = BTreeMap::<DataTypes, usize>::new();
+            let mut stack = vec![CPT::get_root_id()];
+            while let Some(node_id) = stack.pop() {
+                let node = &self.nodes[node_id];
+                *branching_histogram.entry(node.children.len()).or_insert(0) += 1;
+                node.children.iter().for_each(|&child_node_id| depths[child_node_id] = depths[node_id] + 1);
+                stack.extend(node.children.iter().copied());
+                if let Some(data) = node.data {
+                    *depth_histogram.entry(depths[node_id]).or_insert(0) += 1;
+                    // Each sequence going through the node holds its item once
+                    *item_counts.entry(data).or_insert(0) += subtrees.sequences_through(node_id);
+                }
+            }
+
+            let mut length_histogram = BTreeMap::<usize, usize>::new();
+            self.sequences_lookup_table.keys().for_each(|&last_node_id: &NodeId| *length_histogram.entry(depths[last_node_id]).or_insert(0) += 1);
+            let (parents, children) = branching_histogram.iter()
+                .filter(|(&child_count, _)| child_count > 0)
+                .fold((0, 0), |(parents, children), (&child_count, &nodes)| (parents + nodes, children + child_count * nodes));
+            let mut item_frequencies: Vec<(DataTypes, usize)> = item_counts.into_iter().collect();
+            item_frequencies.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+            CptStats {
+                node_count: self.nodes.len(),
+                sequence_count: self.sequences_lookup_table.len(),
+                max_depth: depth_histogram.keys().next_back().copied().unwrap_or(0),
+                depth_histogram,
+                length_histogram,
+                branching_histogram,
+                mean_branching: if parents > 0 { children as f32 / parents as f32 } else { 0.0 },
+                vocabulary_size: self.inverted_index.values.len(),
+                item_frequencies,
+                items_inserted: self.items_inserted,
+                compression_ratio: self.items_inserted.map(|items_inserted| {
+                    if self.nodes.len() > 1 { items_inserted as f32 / (self.nodes.len() - 1) as f32 } else { 0.0 }
+                }),
+                index_node_ids: self.inverted_index.node_ids.iter().map(Vec::len).sum(),
+                memory: self.memory_usage(),
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+pub mod subtrees {
+    // Subtree queries on a CPT<DataTypes>, shared by the pattern mining and the statistics.
+    //
+    // Nodes are numbered in preorder, so that the descendants of a node are the nodes
+    // numbered up to the end of its subtree.
+    use crate::cpt::cpt::CPT;
+    use crate::data_types::data_types::DataTypes;
+    use crate::nodes::nodes::NodeId;
+
+    // The preorder numbering of the nodes, and the number of sequences going through each node
+    pub(crate) struct SubtreeIndex {
+        // Preorder number of each node
+        first: Vec<usize>,
+        // Preorder number of the last node of the subtree of each node
+        last: Vec<usize>,
+        // Number of sequences ending in the subtree of each node
+        sequences: Vec<usize>,
+    }
+
+    impl SubtreeIndex {
+        pub(crate) fn new(cpt: &CPT<DataTypes>) -> SubtreeIndex {
+            let node_count = cpt.nodes.len();
+            let mut first = vec![0; node_count];
+            let mut preorder = Vec::<NodeId>::with_capacity(node_count);
+            let mut stack = vec![CPT::get_root_id()];
+            while let Some(node_id) = stack.pop() {
+                first[node_id] = preorder.len();
+                preorder.push(node_id);
+                stack.extend(cpt.nodes[node_id].children.iter().copied());
+            }
+            // Children are numbered after their parent, the subtrees are summed from the leaves up
+            let mut subtree_sizes = vec![1; node_count];
+            let mut sequences: Vec<usize> = (0..node_count).map(|node_id| usize::from(cpt.sequences_lookup_table.contains_key(&node_id))).collect();
+            for &node_id in preorder.iter().rev() {
+                if let Some(parent_node_id) = cpt.nodes[node_id].parent {
+                    subtree_sizes[parent_node_id] += subtree_sizes[node_id];
+                    sequences[parent_node_id] += sequences[node_id];
+                }
+            }
+            let last = (0..node_count).map(|node_id| first[node_id] + subtree_sizes[node_id] - 1).collect();
+            SubtreeIndex { first, last, sequences }
+        }
+
+        // Keeps the nodes that have none of the other nodes above them, in preorder.
+        pub(crate) fn antichain(&self, mut node_ids: Vec<NodeId>) -> Vec<NodeId> {
+            node_ids.sort_unstable_by_key(|&node_id| self.first[node_id]);
+            let mut antichain = Vec::<NodeId>::with_capacity(node_ids.len());
+            for node_id in node_ids {
+                if antichain.last().is_none_or(|&kept_node_id| !self.is_below(node_id, kept_node_id)) {
+                    antichain.push(node_id);
+                }
+            }
+            antichain
+        }
+
+        // Number of sequences going through the node.
+        pub(crate) fn sequences_through(&self, node_id: NodeId) -> usize {
+            self.sequences[node_id]
+        }
+
+        // Number of sequences going through the nodes of an antichain.
+        pub(crate) fn support(&self, antichain: &[NodeId]) -> usize {
+            antichain.iter().map(|&node_id| self.sequences[node_id]).sum()
+        }
+
+        // Keeps the nodes strictly below a node of the antichain.
+        pub(crate) fn below(&self, antichain: &[NodeId], node_ids: &[NodeId]) -> Vec<NodeId> {
+            node_ids.iter().copied().filter(|&node_id| {
+                // The last node of the antichain numbered before this node is the only possible ancestor
+                let position = antichain.partition_point(|&ancestor_node_id| self.first[ancestor_node_id] < self.first[node_id]);
+                position > 0 && self.is_below(node_id, antichain[position - 1])
+            }).collect()
+        }
+
+        // Whether the node is in the subtree of the other node, or is that node
+        fn is_below(&self, node_id: NodeId, ancestor_node_id: NodeId) -> bool {
+            self.first[ancestor_node_id] <= self.first[node_id] && self.first[node_id] <= self.last[ancestor_node_id]
+        }
+    }
+}
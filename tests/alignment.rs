@@ -0,0 +1,69 @@
+#[cfg(test)]
+mod tests {
+
+    use cpt_rust::alignment::alignment::{EditCosts, dynamic_time_warping, levenshtein, longest_common_subsequence};
+    use cpt_rust::cpt::cpt::{CPT, MatchOptions, SequenceMatchFunction, SequenceRetreiveFunction};
+    use cpt_rust::data_types::data_types::{DataTypes, SimilarityScores};
+
+    fn integers(values: &[usize]) -> Vec<DataTypes> {
+        values.iter().map(|&value| DataTypes::Integer(value)).collect()
+    }
+
+    #[test]
+    fn sequence_metrics() {
+        let kitten = "kitten".bytes().map(DataTypes::U8).collect::<Vec<DataTypes>>();
+        let sitting = "sitting".bytes().map(DataTypes::U8).collect::<Vec<DataTypes>>();
+        assert_eq!(levenshtein(&kitten, &sitting, &EditCosts::default()), 3);
+        assert_eq!(levenshtein(&kitten, &sitting, &EditCosts { insertion: 5, deletion: 1, substitution: 1 }), 7);
+        assert_eq!(levenshtein(&kitten, &[], &EditCosts::default()), 6);
+        assert_eq!(longest_common_subsequence(&kitten, &sitting), 4);
+
+        assert_eq!(dynamic_time_warping(&integers(&[1, 1, 5, 5]), &integers(&[1, 5, 5, 5])).unwrap(), 0.0);
+        assert_eq!(dynamic_time_warping(&integers(&[1, 2]), &integers(&[4])).unwrap(), 5.0);
+        assert!(dynamic_time_warping(&integers(&[1]), &[DataTypes::U8(1)]).is_err());
+    }
+
+    #[test]
+    fn time_shifted_sequences_are_ranked_first() {
+        let mut cpt = CPT::new();
+        cpt.add_sequence_to_root(integers(&[1, 5, 5, 5]), None);
+        cpt.add_sequence_to_root(integers(&[1, 1, 4, 4]), None);
+        let query = integers(&[1, 1, 5, 5]);
+        let options = MatchOptions { retrieve_function: SequenceRetreiveFunction::ExactOnly, max_results: None, ..MatchOptions::default() };
+        let best = |match_function: SequenceMatchFunction| -> Vec<usize> {
+            let matches = cpt.match_sequence_with_options(&query, false, &[match_function], &options).unwrap();
+            assert_eq!(matches.len(), 2);
+            matches.last().unwrap().0.iter().map(|node| node.0).collect()
+        };
+        // Item by item, the shifted sequence is further away
+        assert_eq!(best(SequenceMatchFunction::AlgebraicDistance), vec![1, 5, 6, 7]);
+        assert_eq!(best(SequenceMatchFunction::DynamicTimeWarping), vec![1, 2, 3, 4]);
+        assert_eq!(best(SequenceMatchFunction::LongestCommonSubsequence), vec![1, 2, 3, 4]);
+
+        let matches = cpt.match_sequence(&query, false, &[SequenceMatchFunction::EditDistance(EditCosts::default())]);
+        let (nodes, scores) = matches.last().unwrap();
+        assert_eq!(nodes.len(), 4);
+        assert!(matches!(scores[&SequenceMatchFunction::EditDistance(EditCosts::default())], SimilarityScores::Distance(distance) if distance == 1.0));
+    }
+
+    #[test]
+    fn sequence_metrics_read_past_the_matched_nodes() {
+        let mut cpt = CPT::new();
+        // The query is shifted by one item in the training sequence
+        cpt.add_sequence_to_root(integers(&[1, 2, 3, 3, 4]), None);
+        let query = integers(&[1, 2, 3, 4]);
+        let dtw = [SequenceMatchFunction::DynamicTimeWarping];
+        let score = |options: MatchOptions| -> SimilarityScores {
+            let matches = cpt.match_sequence_with_options(&query, true, &dtw, &options).unwrap();
+            assert_eq!(matches.len(), 1);
+            matches[0].1[&dtw[0]]
+        };
+        let exact_end = MatchOptions { retrieve_function: SequenceRetreiveFunction::ExactOnly, ..MatchOptions::default() };
+        // The window has the length of the query: [2, 3, 3, 4]
+        assert_eq!(score(exact_end.clone()), SimilarityScores::Distance(1.0));
+        // One more item reaches the start of the training sequence
+        assert_eq!(score(MatchOptions { alignment_margin: 1, ..exact_end.clone() }), SimilarityScores::Distance(0.0));
+        // The window stops at the root
+        assert_eq!(score(MatchOptions { alignment_margin: 10, ..exact_end }), SimilarityScores::Distance(0.0));
+    }
+}
@@ -0,0 +1,69 @@
+#[cfg(test)]
+mod tests {
+
+    use serde_json::Value;
+    use cpt_rust::binary::binary::{MAGIC, FORMAT_VERSION};
+    use cpt_rust::cpt::cpt::CPT;
+    use cpt_rust::data_types::data_types::{DataTypes, SequenceAttributes};
+
+    fn trained_cpt() -> CPT<DataTypes> {
+        let mut cpt = CPT::new();
+        cpt.add_sequence_to_root(vec![DataTypes::Integer(1), DataTypes::Integer(2), DataTypes::Integer(300)], None);
+        cpt.add_sequence_to_root(vec![DataTypes::Integer(1), DataTypes::U8(5), DataTypes::Integer(1)],
+            Some(vec![SequenceAttributes::ClassStr("Test".to_string())]));
+        cpt.add_sequence_to_root(vec![DataTypes::U8(5)], None);
+        cpt
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let cpt = trained_cpt();
+        let bytes = cpt.to_binary();
+        assert_eq!(&bytes[..4], b"CPTB");
+
+        let loaded = CPT::read_binary(bytes.as_slice()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&loaded.to_json()).unwrap(),
+            serde_json::from_str::<Value>(&cpt.to_json()).unwrap()
+        );
+        // The same model always gives the same bytes
+        assert_eq!(loaded.to_binary(), bytes);
+    }
+
+    #[test]
+    fn binary_corruption_is_detected() {
+        let bytes = trained_cpt().to_binary();
+
+        let mut corrupted = bytes.clone();
+        let middle = corrupted.len() / 2;
+        corrupted[middle] ^= 0x01;
+        assert!(CPT::read_binary(corrupted.as_slice()).is_err());
+
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(CPT::read_binary(truncated).is_err());
+
+        let error = CPT::read_binary(&b"{\"nodes\": []}"[..]).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn out_of_range_node_ids_are_rejected() {
+        // Varint of the zigzag encoded i64::MAX
+        let largest_delta = [0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01];
+        let header: Vec<u8> = MAGIC.iter().copied().chain(FORMAT_VERSION.to_le_bytes().iter().copied()).collect();
+
+        // Two children ids, each i64::MAX after the previous one, overflow
+        let mut bytes = header.clone();
+        bytes.extend_from_slice(&[1, 0, 0, 2]);
+        bytes.extend_from_slice(&largest_delta);
+        bytes.extend_from_slice(&largest_delta);
+        let error = CPT::read_binary(bytes.as_slice()).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        // A parent id before the first node
+        let mut bytes = header;
+        bytes.extend_from_slice(&[1, 0, 3, 0]);
+        let error = CPT::read_binary(bytes.as_slice()).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
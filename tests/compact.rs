@@ -0,0 +1,57 @@
+#[cfg(test)]
+mod tests {
+
+    use std::mem::size_of;
+    use cpt_rust::compact::compact::{ChildList, CompactCPT};
+    use cpt_rust::cpt::cpt::{CPT, SequenceMatchFunction};
+    use cpt_rust::data_types::data_types::{DataTypes, SequenceAttributes};
+
+    fn byte_sequences() -> Vec<Vec<DataTypes>> {
+        "the quick brown fox jumps over the lazy dog, then the fox sleeps".split(' ')
+            .map(|word| word.bytes().map(DataTypes::U8).collect())
+            .collect()
+    }
+
+    #[test]
+    fn compact_tree_answers_like_the_cpt() {
+        assert_eq!(size_of::<ChildList>(), 16);
+        let mut cpt = CPT::new();
+        let mut compact = CompactCPT::new();
+        for (index, sequence) in byte_sequences().into_iter().enumerate() {
+            let attributes = Some(vec![SequenceAttributes::ClassStr(format!("w{}", index))]);
+            cpt.add_sequence_to_root(sequence.clone(), attributes.clone());
+            compact.add_sequence_to_root(sequence, attributes);
+        }
+        assert_eq!(compact.node_count(), cpt.nodes.len());
+        assert_eq!(compact.sequence_attributes(3).map(|attributes| attributes.len()), Some(3));
+
+        let sorted = |mut predictions: Vec<(DataTypes, usize, f32)>| {
+            predictions.sort_by_key(|prediction| prediction.0);
+            predictions
+        };
+        let converted = cpt.compact();
+        for query in ["th", "fo", "o"].iter() {
+            let query: Vec<DataTypes> = query.bytes().map(DataTypes::U8).collect();
+            let expected = sorted(cpt.predict(&query, 1));
+            assert_eq!(sorted(compact.predict(&query, 1)), expected);
+            assert_eq!(sorted(converted.predict(&query, 1)), expected);
+            assert_eq!(sorted(compact.to_cpt().predict(&query, 1)), expected);
+        }
+        let query = [DataTypes::U8(b'h'), DataTypes::U8(b'e')];
+        assert_eq!(
+            compact.match_sequence(&query, true, &[SequenceMatchFunction::SequenceLength]).len(),
+            cpt.match_sequence(&query, true, &[SequenceMatchFunction::SequenceLength]).len()
+        );
+
+        let mut restored = compact.to_cpt();
+        assert!(restored.validate().is_ok());
+        assert!(restored.inverted_index_is_consistent());
+
+        let cpt_usage = cpt.memory_usage();
+        let compact_usage = compact.memory_usage();
+        assert!(cpt_usage.nodes > 0 && cpt_usage.index > 0 && cpt_usage.lookup_table > 0);
+        assert!(compact_usage.nodes < cpt_usage.nodes);
+        assert!(compact_usage.index < cpt_usage.index);
+        assert_eq!(cpt_usage.total(), cpt_usage.nodes + cpt_usage.index + cpt_usage.lookup_table);
+    }
+}
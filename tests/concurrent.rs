@@ -0,0 +1,70 @@
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+    use std::thread;
+    use cpt_rust::concurrent::concurrent::SharedCPT;
+    use cpt_rust::cpt::cpt::CPT;
+    use cpt_rust::data_types::data_types::DataTypes;
+
+    #[test]
+    fn readers_see_published_snapshots() {
+        let shared = SharedCPT::new(CPT::new(), 0);
+        shared.add_sequence(vec![DataTypes::Integer(1), DataTypes::Integer(2)], None);
+        let before = shared.snapshot();
+        assert_eq!(before.nodes.len(), 1);
+        assert_eq!(shared.version(), 0);
+
+        shared.publish();
+        assert_eq!(shared.version(), 1);
+        assert_eq!(shared.snapshot().nodes.len(), 3);
+        // Snapshots taken earlier are not modified
+        assert_eq!(before.nodes.len(), 1);
+        assert_eq!(shared.predict(&[DataTypes::Integer(1)], 1).unwrap()[0].0, DataTypes::Integer(2));
+    }
+
+    #[test]
+    fn readers_run_while_training() {
+        let shared = SharedCPT::new(CPT::new(), 10);
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for item in 0..100 {
+                    shared.add_sequence(vec![DataTypes::Integer(1), DataTypes::Integer(item + 2)], None);
+                }
+            });
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..100 {
+                        let predictions = shared.predict(&[DataTypes::Integer(1)], 1).unwrap();
+                        let snapshot = shared.snapshot();
+                        assert!(predictions.len() <= snapshot.nodes.len());
+                    }
+                });
+            }
+        });
+        assert_eq!(shared.version(), 10);
+        assert_eq!(shared.predict(&[DataTypes::Integer(1)], 1).unwrap().len(), 100);
+    }
+
+    #[test]
+    fn publications_reuse_the_standby_copy() {
+        let shared = SharedCPT::new(CPT::new(), 1);
+        let first = Arc::as_ptr(&shared.snapshot());
+        shared.add_sequence(vec![DataTypes::Integer(1)], None);
+        let second = Arc::as_ptr(&shared.snapshot());
+        assert_ne!(first, second);
+        // No snapshot of the first copy is held: it is caught up and published again, without a copy
+        shared.add_sequence(vec![DataTypes::Integer(2)], None);
+        assert_eq!(Arc::as_ptr(&shared.snapshot()), first);
+        assert_eq!(shared.snapshot().sequences_lookup_table.len(), 2);
+
+        // A snapshot held across two publications is left as it was, the standby copy is cloned instead
+        let held = shared.snapshot();
+        shared.add_sequence(vec![DataTypes::Integer(3)], None);
+        shared.add_sequence(vec![DataTypes::Integer(4)], None);
+        assert_ne!(Arc::as_ptr(&shared.snapshot()), Arc::as_ptr(&held));
+        assert_eq!(held.sequences_lookup_table.len(), 2);
+        assert_eq!(shared.snapshot().sequences_lookup_table.len(), 4);
+        assert_eq!(shared.version(), 4);
+    }
+}
@@ -3,8 +3,9 @@
 mod tests {
 
     use serde_json::Value;
-    use cpt_rust::cpt::cpt::CPT;
-    use cpt_rust::data_types::data_types::DataTypes;
+    use cpt_rust::cpt::cpt::{CPT, MatchOptions, SequenceMatchFunction, SequenceRetreiveFunction};
+    use cpt_rust::data_types::data_types::{DataTypes, SequenceAttributes, SimilarityScores};
+    use cpt_rust::errors::errors::CptError;
     use cpt_rust::nodes::nodes::{NodeId};
     use std::io::Write;
     use std::fs::File;
@@ -61,4 +62,224 @@ mod tests {
             expected_result
         );
     }
+
+    #[test]
+    fn predict_batch_matches_predict() {
+        let mut cpt = CPT::new();
+        for offset in 0..20 {
+            let sequence: Vec<DataTypes> = (0..5).map(|index| DataTypes::Integer((offset * 3 + index * 7) % 11)).collect();
+            cpt.add_sequence_to_root(sequence, None);
+        }
+        let queries: Vec<Vec<DataTypes>> = (0..30)
+            .map(|offset| (0..3).map(|index| DataTypes::Integer((offset + index * 5) % 13)).collect())
+            .chain(vec![vec![DataTypes::Integer(1)]])
+            .collect();
+
+        let sorted = |mut predictions: Vec<(DataTypes, usize, f32)>| {
+            predictions.sort_by_key(|prediction| prediction.0);
+            predictions
+        };
+        let results = cpt.predict_batch(&queries, 2, 4);
+        assert_eq!(results.len(), queries.len());
+        for (query, result) in queries.iter().zip(results).take(30) {
+            assert_eq!(sorted(result.unwrap()), sorted(cpt.predict(query, 2)));
+        }
+        // The last query is shorter than the prefix
+        assert!(cpt.predict_batch(&queries, 2, 0).last().unwrap().is_err());
+    }
+
+    #[test]
+    fn merged_shards_match_single_tree() {
+        let sequences: Vec<Vec<DataTypes>> = (0..40)
+            .map(|offset| (0..4).map(|index| DataTypes::Integer((offset * 5 + index * 3) % 9)).collect())
+            .collect();
+        let mut single = CPT::new();
+        sequences.iter().for_each(|sequence| single.add_sequence_to_root(sequence.clone(), None));
+
+        let training = sequences.iter().enumerate().map(|(index, sequence)| {
+            (sequence.clone(), Some(vec![SequenceAttributes::ClassStr(format!("s{}", index))]))
+        }).collect();
+        let mut merged = CPT::train_parallel(training, 3).unwrap();
+        assert!(merged.validate().is_ok());
+        assert!(merged.inverted_index_is_consistent());
+        assert_eq!(merged.nodes.len(), single.nodes.len());
+        assert_eq!(merged.sequences_lookup_table.len(), single.sequences_lookup_table.len());
+        // Attributes of identical sequences from different shards are kept
+        assert_eq!(merged.sequences_lookup_table.values().map(|attributes| attributes.len()).sum::<usize>(), 40);
+
+        let query = [DataTypes::Integer(3), DataTypes::Integer(6)];
+        let sorted = |mut predictions: Vec<(DataTypes, usize, f32)>| {
+            predictions.sort_by_key(|prediction| prediction.0);
+            predictions
+        };
+        assert_eq!(sorted(merged.predict(&query, 2)), sorted(single.predict(&query, 2)));
+
+        // An inconsistent tree is not merged
+        let mut other = CPT::new();
+        other.add_sequence_to_root(vec![DataTypes::Integer(1), DataTypes::Integer(2)], None);
+        other.nodes[0].children.push(7);
+        let node_count = merged.nodes.len();
+        assert!(merged.merge(&other).is_err());
+        assert_eq!(merged.nodes.len(), node_count);
+    }
+
+    #[test]
+    fn high_fan_out_nodes_keep_unique_children() {
+        let mut cpt = CPT::new();
+        for _ in 0..2 {
+            for byte in 0..=255u8 {
+                cpt.add_sequence_to_root(vec![DataTypes::U8(7), DataTypes::U8(byte)], None);
+            }
+        }
+        assert_eq!(cpt.nodes.len(), 258);
+        assert_eq!(cpt.child_exists(DataTypes::U8(200), 1), Some(202));
+        assert_eq!(cpt.child_exists(DataTypes::Integer(200), 1), None);
+
+        // The child index is not saved, loaded models are indexed again while training
+        let json = cpt.to_json();
+        assert!(!json.contains("child_index"));
+        let mut loaded = CPT::from_json(&json).unwrap();
+        loaded.add_sequence_to_root(vec![DataTypes::U8(7), DataTypes::U8(9)], None);
+        loaded.add_sequence_to_root(vec![DataTypes::U8(7), DataTypes::U8(9), DataTypes::U8(1)], None);
+        assert_eq!(loaded.nodes.len(), 259);
+        assert_eq!(loaded.nodes[1].children.len(), 256);
+    }
+
+    #[test]
+    fn distance_windows_on_the_inverted_index() {
+        let mut cpt = CPT::new();
+        for value in [10, 3, 7, 20, 8, 12].iter() {
+            cpt.add_sequence_to_root(vec![DataTypes::Integer(*value), DataTypes::U8(1)], None);
+        }
+        let index = &cpt.inverted_index;
+        assert_eq!(index.get_values_within(DataTypes::Integer(9), 1.0).unwrap(), vec![(DataTypes::Integer(8), 1.0), (DataTypes::Integer(10), 1.0)]);
+        assert_eq!(index.get_closest_values(DataTypes::Integer(11), 3).unwrap(), vec![
+            (DataTypes::Integer(10), 1.0), (DataTypes::Integer(12), 1.0), (DataTypes::Integer(8), 3.0)
+        ]);
+        // U8 values are sorted after the integers, the window does not reach them
+        assert_eq!(index.get_closest_values(DataTypes::Integer(30), 10).unwrap().len(), 6);
+        assert!(index.get_closest_values(DataTypes::U8(1), 1).is_err());
+
+        let retrieved = index.get_retrieved_value_ids(
+            &[SequenceMatchFunction::StrictEqual, SequenceMatchFunction::AlgebraicDistance],
+            DataTypes::Integer(7), SequenceRetreiveFunction::TopNSimilarValues(2)
+        ).unwrap();
+        let mut node_ids: Vec<usize> = retrieved.keys().copied().collect();
+        node_ids.sort();
+        assert_eq!(node_ids, vec![5, 9]);
+        assert!(matches!(retrieved[&5][0], (SequenceMatchFunction::StrictEqual, SimilarityScores::IsEqual(true))));
+        assert!(matches!(retrieved[&9][1], (SequenceMatchFunction::AlgebraicDistance, SimilarityScores::Distance(distance)) if distance == 1.0));
+    }
+
+    #[test]
+    fn match_sequence_retrieval_strategies() {
+        let mut cpt = CPT::new();
+        for sequence in [[1, 2], [5, 6], [9, 2], [2, 3]].iter() {
+            cpt.add_sequence_to_root(sequence.iter().map(|&value| DataTypes::Integer(value)).collect(), None);
+        }
+        let query = [DataTypes::Integer(1), DataTypes::Integer(2)];
+        let distance = [SequenceMatchFunction::AlgebraicDistance];
+        let matched_nodes = |options: MatchOptions| -> Vec<Vec<usize>> {
+            cpt.match_sequence_with_options(&query, false, &distance, &options).unwrap().into_iter()
+                .map(|(nodes, _)| nodes.iter().map(|node| node.0).collect())
+                .collect()
+        };
+        // Every node with children starts a match by default
+        assert_eq!(matched_nodes(MatchOptions::default()).len(), 4);
+        assert_eq!(cpt.match_sequence(&query, false, &distance).len(), 4);
+        assert_eq!(matched_nodes(MatchOptions { retrieve_function: SequenceRetreiveFunction::ExactOnly, max_results: None, ..MatchOptions::default() }), vec![vec![1, 2]]);
+        assert_eq!(matched_nodes(MatchOptions { retrieve_function: SequenceRetreiveFunction::TopNSimilarValues(2), max_results: None, ..MatchOptions::default() }).len(), 2);
+        assert_eq!(matched_nodes(MatchOptions { retrieve_function: SequenceRetreiveFunction::WithinDistance(4.0), max_results: None, ..MatchOptions::default() }).len(), 3);
+        // Only the closest match is kept
+        assert_eq!(matched_nodes(MatchOptions { retrieve_function: SequenceRetreiveFunction::AllValues, max_results: Some(1), ..MatchOptions::default() }), vec![vec![1, 2]]);
+        assert!(cpt.match_sequence_with_options(&[DataTypes::U8(1)], false, &distance, &MatchOptions {
+            retrieve_function: SequenceRetreiveFunction::TopNSimilarValues(2), max_results: None, ..MatchOptions::default()
+        }).is_err());
+    }
+
+    #[test]
+    fn forward_beam_search() {
+        // Long pseudo-random sequences over a few values, so that matches branch a lot
+        let mut cpt = CPT::new();
+        let mut state: usize = 7;
+        let mut sequences = Vec::<Vec<DataTypes>>::new();
+        for _ in 0..60 {
+            let sequence: Vec<DataTypes> = (0..24).map(|_| {
+                state = (state * 1103515245 + 12345) % 2147483648;
+                DataTypes::Integer(state / 65536 % 4)
+            }).collect();
+            cpt.add_sequence_to_root(sequence.clone(), None);
+            sequences.push(sequence);
+        }
+        let query = &sequences[42][2..22];
+        let distance = [SequenceMatchFunction::AlgebraicDistance];
+        let beam = MatchOptions { beam_width: Some(8), ..MatchOptions::default() };
+        let matches = cpt.match_sequence_with_options(query, false, &distance, &beam).unwrap();
+        assert!(!matches.is_empty() && matches.len() <= 8);
+        assert!(matches.iter().all(|(nodes, _)| nodes.len() == 20));
+        let unpruned = cpt.match_sequence(query, false, &distance);
+        assert!(unpruned.len() > matches.len());
+        assert_eq!(unpruned.last().unwrap().1[&distance[0]], SimilarityScores::Distance(0.0));
+        // A wide enough beam keeps the exact match among the many partial matches that tie early
+        let wide_beam = MatchOptions { beam_width: Some(500), ..MatchOptions::default() };
+        let matches = cpt.match_sequence_with_options(query, false, &distance, &wide_beam).unwrap();
+        assert_eq!(matches.last().unwrap().1[&distance[0]], SimilarityScores::Distance(0.0));
+
+        // Partial matches further than the threshold are dropped
+        let threshold = MatchOptions { score_threshold: Some(0.0), ..MatchOptions::default() };
+        let exact = cpt.match_sequence_with_options(query, false, &distance, &threshold).unwrap();
+        assert!(!exact.is_empty());
+        assert!(exact.iter().all(|(_, scores)| scores[&distance[0]] == SimilarityScores::Distance(0.0)));
+        let exact_metrics = cpt.match_sequence_with_metrics(query, false, &[&distance[0]], &threshold).unwrap();
+        assert_eq!(exact_metrics.len(), exact.len());
+    }
+
+    #[test]
+    fn gapped_matching() {
+        let mut cpt = CPT::new();
+        // A noise item between 1 and 2
+        cpt.add_sequence_to_root(vec![DataTypes::Integer(1), DataTypes::Integer(9), DataTypes::Integer(2), DataTypes::Integer(3)], None);
+        cpt.add_sequence_to_root(vec![DataTypes::Integer(1), DataTypes::Integer(2), DataTypes::Integer(7)], None);
+        let query = [DataTypes::Integer(1), DataTypes::Integer(2), DataTypes::Integer(3)];
+        let distance = [SequenceMatchFunction::AlgebraicDistance];
+        let best = |backwards: bool, max_gap: usize| -> (Vec<usize>, SimilarityScores) {
+            let options = MatchOptions { retrieve_function: SequenceRetreiveFunction::ExactOnly, max_gap, skip_penalty: 0.5, ..MatchOptions::default() };
+            let (nodes, scores) = cpt.match_sequence_with_options(&query, backwards, &distance, &options).unwrap().pop().unwrap();
+            (nodes.iter().map(|node| node.0).collect(), scores[&distance[0]])
+        };
+        assert_eq!(best(false, 0), (vec![1, 5, 6], SimilarityScores::Distance(4.0)));
+        // Skipping the noise item costs the penalty only
+        assert_eq!(best(false, 1), (vec![1, 3, 4], SimilarityScores::Distance(0.5)));
+        assert_eq!(best(true, 0), (vec![2, 3, 4], SimilarityScores::Distance(8.0)));
+        assert_eq!(best(true, 1), (vec![1, 3, 4], SimilarityScores::Distance(0.5)));
+        // Gaps larger than the depth of the tree stop at the leaves and the root
+        assert_eq!(best(false, usize::MAX), (vec![1, 3, 4], SimilarityScores::Distance(0.5)));
+        assert_eq!(best(true, usize::MAX), (vec![1, 3, 4], SimilarityScores::Distance(0.5)));
+
+        // Lengths and equality cannot be penalized, a skip with a penalty is an error
+        let length = [SequenceMatchFunction::SequenceLength];
+        let gapped = MatchOptions { retrieve_function: SequenceRetreiveFunction::ExactOnly, max_gap: 1, skip_penalty: 0.5, ..MatchOptions::default() };
+        assert!(matches!(cpt.match_sequence_with_options(&query, false, &length, &gapped), Err(CptError::IncompatibleTypes(_))));
+        assert!(cpt.match_sequence_with_options(&query, false, &length, &MatchOptions { skip_penalty: 0.0, ..gapped }).is_ok());
+    }
+
+    #[test]
+    fn incompatible_types_are_errors() {
+        let mut cpt = CPT::new();
+        cpt.add_sequence_to_root(vec![DataTypes::Integer(1), DataTypes::Integer(2)], None);
+        cpt.add_sequence_to_root(vec![DataTypes::U8(1), DataTypes::U8(2)], None);
+        let distance = [SequenceMatchFunction::AlgebraicDistance];
+        let query = [DataTypes::Integer(1), DataTypes::Integer(2)];
+        // The U8 nodes cannot be compared with the integers of the query
+        assert!(cpt.inverted_index.try_get_similar_value_ids(&distance, query[0]).is_err());
+        for backwards in [false, true] {
+            assert!(matches!(cpt.match_sequence_with_options(&query, backwards, &distance, &MatchOptions::default()), Err(CptError::IncompatibleTypes(_))));
+        }
+        // The error can also come up during the walk, after an exact start
+        cpt.add_sequence_to_root(vec![DataTypes::Integer(1), DataTypes::U8(2)], None);
+        let exact_start = MatchOptions { retrieve_function: SequenceRetreiveFunction::ExactOnly, ..MatchOptions::default() };
+        assert!(matches!(cpt.match_sequence_with_options(&query, false, &distance, &exact_start), Err(CptError::IncompatibleTypes(_))));
+        // Strict equality only looks at equal values
+        assert!(cpt.match_sequence_with_options(&query, false, &[SequenceMatchFunction::StrictEqual], &MatchOptions::default()).is_ok());
+    }
 }
@@ -0,0 +1,66 @@
+#[cfg(test)]
+mod tests {
+
+    use std::sync::{Arc, Mutex};
+    use cpt_rust::cpt::cpt::{CPT, SequenceMatchFunction};
+    use cpt_rust::data_types::data_types::DataTypes;
+    use cpt_rust::diagnostics::diagnostics::DiagnosticEvent;
+
+    #[test]
+    fn events_are_sent_to_the_hook() {
+        let seq1: [DataTypes; 3] = [DataTypes::Integer(1), DataTypes::Integer(2), DataTypes::Integer(3)];
+        let seq2: [DataTypes; 3] = [DataTypes::Integer(1), DataTypes::Integer(5), DataTypes::Integer(6)];
+        let mut cpt = CPT::new();
+        assert!(!cpt.diagnostics.is_enabled());
+        cpt.add_sequence_to_root(seq1.to_vec(), None);
+        cpt.add_sequence_to_root(seq2.to_vec(), None);
+
+        let events = Arc::new(Mutex::new(Vec::<String>::new()));
+        let hook_events = events.clone();
+        cpt.set_diagnostics(move |event: &DiagnosticEvent<'_>| {
+            let name = match event {
+                DiagnosticEvent::PredictionPrefix { prefix_values, .. } => format!("prefix {}", prefix_values.len()),
+                DiagnosticEvent::PrefixValueSequences { sequence_ids, .. } => format!("sequences {}", sequence_ids.len()),
+                DiagnosticEvent::Consequent { .. } => "consequent".to_string(),
+                DiagnosticEvent::ConsequentSupport { .. } => "support".to_string(),
+                DiagnosticEvent::Predictions { predictions } => format!("predictions {}", predictions.len()),
+                DiagnosticEvent::MatchSequence { .. } => "match".to_string(),
+                DiagnosticEvent::SequenceMatches { results } => format!("matches {}", results.len()),
+            };
+            hook_events.lock().unwrap().push(name);
+        });
+
+        cpt.predict(&[DataTypes::Integer(1)], 1);
+        assert_eq!(*events.lock().unwrap(), vec![
+            "prefix 1", "sequences 2", "consequent", "consequent", "support", "predictions 4"
+        ]);
+
+        events.lock().unwrap().clear();
+        cpt.match_sequence(&[DataTypes::Integer(1), DataTypes::Integer(2)], false, &[SequenceMatchFunction::SequenceLength]);
+        assert_eq!(*events.lock().unwrap(), vec!["match", "matches 4"]);
+    }
+
+    #[test]
+    fn consequents_are_reported_in_tree_order() {
+        let mut cpt = CPT::new();
+        for sequence in [vec![1, 2, 3, 4], vec![1, 5]].iter() {
+            cpt.add_sequence_to_root(sequence.iter().map(|&value| DataTypes::Integer(value)).collect(), None);
+        }
+        let consequents = Arc::new(Mutex::new(Vec::<(usize, Vec<usize>)>::new()));
+        let hook_consequents = consequents.clone();
+        cpt.set_diagnostics(move |event: &DiagnosticEvent<'_>| {
+            if let DiagnosticEvent::Consequent { sequence_id, consequent, .. } = event {
+                hook_consequents.lock().unwrap().push((*sequence_id, consequent.to_vec()));
+            }
+        });
+
+        let predictions = cpt.predict(&[DataTypes::Integer(1)], 1);
+        // The walk goes up from the end of each sequence and the consequent is reversed once it is done.
+        // It used to be reversed after every item, which reported 2, 4, 3 for the first sequence.
+        assert_eq!(*consequents.lock().unwrap(), vec![(4, vec![2, 3, 4]), (5, vec![5])]);
+        // The predictions only count the items, they are the same either way
+        let mut items: Vec<(DataTypes, usize)> = predictions.iter().map(|&(item, support, _confidence)| (item, support)).collect();
+        items.sort();
+        assert_eq!(items, vec![(DataTypes::Integer(2), 1), (DataTypes::Integer(3), 1), (DataTypes::Integer(4), 1), (DataTypes::Integer(5), 1)]);
+    }
+}
@@ -0,0 +1,85 @@
+#[cfg(test)]
+mod tests {
+
+    use cpt_rust::cpt::cpt::{CPT, MatchOptions, SequenceMatchFunction};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+    use cpt_rust::data_types::data_types::{DataTypes, SequenceAttributes};
+    use cpt_rust::diagnostics::diagnostics::DiagnosticEvent;
+    use cpt_rust::dot::dot::DotOptions;
+
+    fn trained_cpt() -> CPT<DataTypes> {
+        let mut cpt = CPT::new();
+        for (sequence, class) in [(vec![1, 2, 4, 9], "a"), (vec![1, 5, 7], "b"), (vec![3, 2, 4], "c"), (vec![8, 8, 8, 8, 8], "d")].iter() {
+            cpt.add_sequence_to_root(
+                sequence.iter().map(|&value| DataTypes::Integer(value)).collect(),
+                Some(vec![SequenceAttributes::ClassStr(class.to_string())])
+            );
+        }
+        cpt
+    }
+
+    #[test]
+    fn match_to_dot() {
+        let cpt = trained_cpt();
+        let query = [DataTypes::Integer(1), DataTypes::Integer(2)];
+        let functions = [SequenceMatchFunction::SequenceLength];
+        let match_options = MatchOptions { max_results: Some(1), ..MatchOptions::default() };
+        let dot = cpt.match_to_dot(&query, false, &functions, &match_options, &DotOptions { depth_limit: 0, cluster_by_attributes: false }).unwrap();
+        assert!(dot.starts_with("digraph Focus {\n"));
+        assert!(dot.contains("1[label=\"ID=1, Some(Integer(1))\\nmatch 0: SequenceLength: Length(1)\"; style=filled; fillcolor=\"lightblue\"];"));
+        assert!(dot.contains("2[label=\"ID=2, Some(Integer(2))\\nmatch 0: SequenceLength: Length(1)\"; style=filled; fillcolor=\"lightblue\"];"));
+        assert!(dot.contains("1 -> 2;"));
+        assert!(dot.contains("note0[label=\"match 0\\nSequenceLength: Length(2)\"; shape=\"rectangle\"];\nnote0 -> 2 [style=dashed];"));
+        // Without depth, only the matched nodes are drawn
+        assert!(!dot.contains("\n0[") && !dot.contains("\n3["));
+
+        let dot = cpt.match_to_dot(&query, false, &functions, &match_options, &DotOptions::default()).unwrap();
+        assert!(dot.contains("\n0[label=\"ID=0, None\"; style=filled; fillcolor=\"white\"];"));
+        assert!(dot.contains("\n4[") && dot.contains("\n6["));
+        assert!(!dot.contains("\n10["));
+    }
+
+    #[test]
+    fn predict_to_dot() {
+        let cpt = trained_cpt();
+        let query = [DataTypes::Integer(1), DataTypes::Integer(2)];
+        let dot = cpt.predict_to_dot(&query, 2, &DotOptions { depth_limit: 0, cluster_by_attributes: false }).unwrap();
+        // Item 4 ends two consequents, it is the best prediction
+        assert_eq!(cpt.predict(&query, 2)[0], (DataTypes::Integer(4), 2, 1.0));
+        assert!(dot.contains("3[label=\"ID=3, Some(Integer(4))\\nprediction 0: support 2, confidence 1.000\"; style=filled; fillcolor=\"orange\"];"));
+        assert!(dot.contains("9[label=\"ID=9, Some(Integer(4))\\nprediction 0: support 2, confidence 1.000\"; style=filled; fillcolor=\"orange\"];"));
+        // The other predictions tie, their order is not checked
+        let node_line = dot.lines().find(|line| line.starts_with("6[")).unwrap();
+        assert!(node_line.starts_with("6[label=\"ID=6, Some(Integer(7))\\nprediction "));
+        assert!(node_line.ends_with(": support 1, confidence 1.000\"; style=filled; fillcolor=\"lightyellow\"];"));
+        // The consequents start after the items of the query
+        assert!(dot.contains("8[label=\"ID=8, Some(Integer(2))\"; style=filled; fillcolor=\"lightblue\"];"));
+        assert!(!dot.contains("\n7[") && !dot.contains("\n10["));
+        assert!(dot.contains("note0[label=\"predictions\\n0: Integer(4) (support 2, confidence 1.000)"));
+
+        // The hook of the CPT still receives the events of predict
+        let mut cpt = trained_cpt();
+        let consequents = Arc::new(AtomicUsize::new(0));
+        let counted_consequents = Arc::clone(&consequents);
+        cpt.set_diagnostics(move |event: &DiagnosticEvent<'_>| {
+            if let DiagnosticEvent::Consequent { .. } = event {
+                counted_consequents.fetch_add(1, AtomicOrdering::SeqCst);
+            }
+        });
+        assert!(cpt.predict_to_dot(&query, 2, &DotOptions::default()).unwrap().contains("fillcolor=\"orange\""));
+        assert_eq!(consequents.load(AtomicOrdering::SeqCst), 3);
+    }
+
+    #[test]
+    fn cluster_by_attributes() {
+        let cpt = trained_cpt();
+        let dot = cpt.predict_to_dot(&[DataTypes::Integer(1), DataTypes::Integer(2)], 2, &DotOptions { depth_limit: 1, cluster_by_attributes: true }).unwrap();
+        assert!(dot.contains("subgraph cluster_attr0 {\nlabel=\"ClassStr(\\\"a\\\")\";\n4["));
+        assert!(dot.contains("subgraph cluster_attr1 {\nlabel=\"ClassStr(\\\"b\\\")\";\n6["));
+        assert!(dot.contains("subgraph cluster_attr2 {\nlabel=\"ClassStr(\\\"c\\\")\";\n9["));
+        assert!(!dot.contains("ClassStr(\\\"d\\\")"));
+        // Clustered nodes are drawn once
+        assert_eq!(dot.matches("\n4[").count(), 1);
+    }
+}
@@ -0,0 +1,34 @@
+#[cfg(test)]
+mod tests {
+
+    use cpt_rust::cpt::cpt::{CPT, SequenceMatchFunction};
+    use cpt_rust::data_types::data_types::{DataTypes, SimilarityScores};
+    use cpt_rust::errors::errors::CptError;
+    use cpt_rust::nodes::nodes::Node;
+
+    #[test]
+    fn bad_inputs_return_errors() {
+        let similarity = DataTypes::Integer(1).try_compute_similarity(SequenceMatchFunction::AlgebraicDistance, DataTypes::U8(1));
+        assert!(matches!(similarity, Err(CptError::IncompatibleTypes(_))));
+        assert!(matches!(
+            DataTypes::Integer(1).try_compute_similarity(SequenceMatchFunction::AlgebraicDistance, DataTypes::Integer(4)),
+            Ok(SimilarityScores::Distance(distance)) if distance == 3.0
+        ));
+
+        assert!(SimilarityScores::Distance(1.0).try_cmp(&SimilarityScores::Length(1)).is_err());
+        assert!(SimilarityScores::None.try_get_zero().is_err());
+        assert!(SimilarityScores::Length(1).try_add(SimilarityScores::IsEqual(true)).is_err());
+        assert_eq!(SimilarityScores::Length(1).try_add(SimilarityScores::Length(2)).unwrap(), SimilarityScores::Length(3));
+
+        let mut cpt = CPT::new();
+        cpt.add_sequence_to_root(vec![DataTypes::Integer(1), DataTypes::Integer(2)], None);
+        assert!(matches!(cpt.try_get_data(7), Err(CptError::UnknownNode(7))));
+        assert_eq!(cpt.try_get_data(2).unwrap(), Some(DataTypes::Integer(2)));
+        assert!(matches!(cpt.try_update_node(7, Node { parent: None, children: vec![], data: None }), Err(CptError::UnknownNode(7))));
+        assert!(matches!(
+            cpt.try_predict(&[DataTypes::Integer(1)], 3),
+            Err(CptError::InvalidPrefix { prefix_length: 3, sequence_length: 1 })
+        ));
+        assert_eq!(cpt.try_predict(&[DataTypes::Integer(1)], 1).unwrap(), vec![(DataTypes::Integer(2), 1, 1.0)]);
+    }
+}
@@ -0,0 +1,62 @@
+#[cfg(test)]
+mod tests {
+
+    use cpt_rust::cpt::cpt::{CPT, SequenceMatchFunction};
+    use cpt_rust::data_types::data_types::{DataTypes, SequenceAttributes};
+    use cpt_rust::errors::errors::CptError;
+
+    fn trained_cpt() -> CPT<DataTypes> {
+        let mut cpt = CPT::new();
+        cpt.add_sequence_to_root(vec![DataTypes::Integer(1), DataTypes::Integer(5), DataTypes::Integer(6)], None);
+        cpt.add_sequence_to_root(vec![DataTypes::Integer(1), DataTypes::Integer(2), DataTypes::Integer(3)], None);
+        cpt.add_sequence_to_root(vec![DataTypes::Integer(4), DataTypes::Integer(2), DataTypes::Integer(1)], Some(vec![SequenceAttributes::ClassStr("a".to_string())]));
+        cpt
+    }
+
+    #[test]
+    fn frozen_tree_answers_like_the_cpt() {
+        let cpt = trained_cpt();
+        let frozen = cpt.freeze();
+        assert_eq!(frozen.node_count(), cpt.nodes.len());
+
+        // Children are sorted by data, and found with a binary search
+        assert_eq!(frozen.children(1), &[4, 2]);
+        assert_eq!(frozen.child(1, DataTypes::Integer(2)).unwrap(), Some(4));
+        assert_eq!(frozen.child(1, DataTypes::Integer(7)).unwrap(), None);
+        // An inconsistent tree is reported instead of panicking
+        let mut corrupt = cpt.clone();
+        corrupt.nodes[2].data = None;
+        assert!(matches!(corrupt.freeze().child(1, DataTypes::Integer(2)), Err(CptError::CorruptModel(_))));
+        assert_eq!(frozen.parent(4), Some(1));
+        assert_eq!(frozen.parent(0), None);
+        assert_eq!(frozen.sequence_attributes(8), Some(&[SequenceAttributes::ClassStr("a".to_string())][..]));
+        assert_eq!(frozen.sequence_attributes(7), None);
+
+        let sequences = [
+            vec![DataTypes::Integer(1)],
+            vec![DataTypes::Integer(4), DataTypes::Integer(2)],
+            vec![DataTypes::Integer(2), DataTypes::Integer(3)],
+        ];
+        for sequence in sequences.iter() {
+            let mut expected = cpt.predict(sequence, 1);
+            let mut predictions = frozen.predict(sequence, 1);
+            expected.sort_by_key(|prediction| prediction.0);
+            predictions.sort_by_key(|prediction| prediction.0);
+            assert_eq!(predictions, expected);
+        }
+        assert!(frozen.try_predict(&[DataTypes::Integer(1)], 2).is_err());
+
+        for &backwards in [false, true].iter() {
+            let sequence = [DataTypes::Integer(1), DataTypes::Integer(2)];
+            let mut expected: Vec<Vec<usize>> = cpt.match_sequence(&sequence, backwards, &[SequenceMatchFunction::AlgebraicDistance]).into_iter()
+                .map(|(nodes, _)| nodes.iter().map(|node| node.0).collect())
+                .collect();
+            let mut matches: Vec<Vec<usize>> = frozen.match_sequence(&sequence, backwards, &[SequenceMatchFunction::AlgebraicDistance]).into_iter()
+                .map(|(nodes, _)| nodes.iter().map(|node| node.0).collect())
+                .collect();
+            expected.sort();
+            matches.sort();
+            assert_eq!(matches, expected);
+        }
+    }
+}
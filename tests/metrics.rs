@@ -0,0 +1,67 @@
+#[cfg(test)]
+mod tests {
+
+    use cpt_rust::cpt::cpt::{CPT, MatchOptions, SequenceMatchFunction};
+    use cpt_rust::data_types::data_types::DataTypes;
+    use cpt_rust::errors::errors::CptError;
+    use cpt_rust::metrics::metrics::{ScoreKind, SimilarityMetric};
+
+    // Rewards items of the same parity, the aggregated score is the product of the item scores
+    struct SameParity;
+
+    impl SimilarityMetric for SameParity {
+        fn name(&self) -> String {
+            "same parity".to_string()
+        }
+
+        fn kind(&self) -> ScoreKind {
+            ScoreKind::Reward
+        }
+
+        fn score(&self, node_value: DataTypes, query_value: DataTypes) -> Result<f32, CptError> {
+            match (node_value, query_value) {
+                (DataTypes::Integer(a), DataTypes::Integer(b)) => Ok(if a % 2 == b % 2 { 1.0 } else { 0.5 }),
+                _ => Err(CptError::IncompatibleTypes("Parity of integers only".to_string()))
+            }
+        }
+
+        fn zero(&self) -> f32 {
+            1.0
+        }
+
+        fn aggregate(&self, total: f32, score: f32) -> f32 {
+            total * score
+        }
+    }
+
+    fn integers(values: &[usize]) -> Vec<DataTypes> {
+        values.iter().map(|&value| DataTypes::Integer(value)).collect()
+    }
+
+    #[test]
+    fn user_defined_metrics_rank_matches() {
+        let mut cpt = CPT::new();
+        cpt.add_sequence_to_root(integers(&[1, 3, 5]), None);
+        cpt.add_sequence_to_root(integers(&[2, 4, 7]), None);
+        let query = integers(&[2, 4]);
+
+        let matches = cpt.match_sequence_with_metrics(&query, false, &[&SameParity], &MatchOptions::default()).unwrap();
+        let (best_nodes, best_scores) = matches.last().unwrap();
+        assert_eq!(best_nodes.iter().map(|node| node.0).collect::<Vec<usize>>(), vec![4, 5]);
+        assert_eq!(best_scores, &vec![1.0]);
+        assert_eq!(matches.iter().map(|(_, scores)| scores[0]).collect::<Vec<f32>>(), vec![0.25, 0.25, 0.5, 1.0]);
+
+        // The built-in functions are metrics too, and rank like match_sequence
+        let distance = SequenceMatchFunction::AlgebraicDistance;
+        assert_eq!(distance.kind(), ScoreKind::Cost);
+        let with_metrics = cpt.match_sequence_with_metrics(&query, false, &[&distance, &SameParity], &MatchOptions::default()).unwrap();
+        let expected = cpt.match_sequence(&query, false, &[distance]);
+        assert_eq!(with_metrics.len(), expected.len());
+        assert_eq!(with_metrics.last().unwrap().0.iter().map(|node| node.0).collect::<Vec<usize>>(), vec![4, 5]);
+
+        let ids = cpt.inverted_index.get_similar_value_ids_with_metrics(&[&SameParity], DataTypes::Integer(4)).unwrap();
+        assert_eq!(ids[&4], vec![1.0]);
+        assert_eq!(ids[&1], vec![0.5]);
+        assert!(cpt.match_sequence_with_metrics(&[DataTypes::U8(1)], false, &[&SameParity], &MatchOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+#[cfg(test)]
+mod tests {
+
+    use cpt_rust::cpt::cpt::CPT;
+    use cpt_rust::data_types::data_types::DataTypes;
+    use cpt_rust::mining::mining::{FrequentPattern, MiningOptions, RuleOptions, rules_to_json};
+
+    fn integers(values: &[usize]) -> Vec<DataTypes> {
+        values.iter().map(|&value| DataTypes::Integer(value)).collect()
+    }
+
+    fn trained_cpt() -> CPT<DataTypes> {
+        let mut cpt = CPT::new();
+        for sequence in [vec![1, 2, 3], vec![1, 2, 4], vec![2, 3, 5], vec![1, 3], vec![7, 7, 7]].iter() {
+            cpt.add_sequence_to_root(integers(sequence), None);
+        }
+        cpt
+    }
+
+    fn pattern(items: &[usize], support: usize) -> FrequentPattern {
+        FrequentPattern { items: integers(items), support }
+    }
+
+    #[test]
+    fn frequent_ngrams() {
+        let cpt = trained_cpt();
+        let ngrams = cpt.frequent_ngrams(&MiningOptions { min_support: 2, max_length: 3, top_k: 10 });
+        assert_eq!(ngrams, vec![pattern(&[1], 3), pattern(&[2], 3), pattern(&[3], 3), pattern(&[1, 2], 2), pattern(&[2, 3], 2)]);
+        // Repeated items of a sequence are counted once
+        let ngrams = cpt.frequent_ngrams(&MiningOptions { min_support: 1, max_length: 3, top_k: 100 });
+        assert!(ngrams.contains(&pattern(&[7, 7], 1)));
+        assert!(ngrams.contains(&pattern(&[7, 7, 7], 1)));
+        assert!(ngrams.contains(&pattern(&[7], 1)));
+        assert!(ngrams.iter().all(|ngram| ngram.items.len() <= 3));
+
+        let no_items = MiningOptions { min_support: 1, max_length: 0, top_k: 100 };
+        assert!(cpt.frequent_ngrams(&no_items).is_empty());
+        assert!(cpt.frequent_subsequences(&no_items).is_empty());
+    }
+
+    #[test]
+    fn frequent_subsequences() {
+        let cpt = trained_cpt();
+        let options = MiningOptions { min_support: 2, max_length: 3, top_k: 10 };
+        assert_eq!(cpt.frequent_subsequences(&options), vec![
+            pattern(&[1], 3), pattern(&[2], 3), pattern(&[3], 3),
+            pattern(&[1, 2], 2), pattern(&[1, 3], 2), pattern(&[2, 3], 2),
+        ]);
+        assert_eq!(cpt.frequent_subsequences(&MiningOptions { top_k: 4, ..options }).len(), 4);
+        let options = MiningOptions { min_support: 1, max_length: 2, top_k: 100 };
+        let subsequences = cpt.frequent_subsequences(&options);
+        assert!(subsequences.contains(&pattern(&[1, 4], 1)));
+        assert!(subsequences.contains(&pattern(&[7, 7], 1)));
+        assert!(!subsequences.contains(&pattern(&[3, 1], 1)));
+        assert!(subsequences.iter().all(|subsequence| subsequence.items.len() <= 2));
+    }
+
+    #[test]
+    fn sequential_rules() {
+        let mut cpt = CPT::new();
+        for sequence in [vec![1, 2, 3], vec![1, 3], vec![2, 1, 3], vec![1, 2], vec![4]].iter() {
+            cpt.add_sequence_to_root(integers(sequence), None);
+        }
+        let rules = cpt.sequential_rules(&RuleOptions { min_support: 2, min_confidence: 0.6, min_lift: 0.0, max_antecedent: 2 });
+        let summary: Vec<(Vec<DataTypes>, DataTypes, usize)> = rules.iter().map(|rule| (rule.antecedent.clone(), rule.consequent, rule.support)).collect();
+        assert_eq!(summary, vec![
+            (integers(&[1]), DataTypes::Integer(3), 3),
+            (integers(&[1, 2]), DataTypes::Integer(3), 2),
+            (integers(&[2]), DataTypes::Integer(3), 2),
+        ]);
+        assert_eq!(rules[0].confidence, 0.75);
+        assert!((rules[0].lift - 1.25).abs() < 1e-6);
+        assert_eq!(rules[0].to_string(), "Integer(1) => Integer(3) (support 3, confidence 0.750, lift 1.250)");
+        // No antecedent of three items is frequent, larger limits stop growing them
+        let options = RuleOptions { min_support: 2, min_confidence: 0.6, min_lift: 0.0, max_antecedent: usize::MAX };
+        assert_eq!(cpt.sequential_rules(&options), rules);
+
+        // 1 is followed by 2 in two of the four sequences holding 1
+        let rules = cpt.sequential_rules(&RuleOptions { min_confidence: 0.0, max_antecedent: 1, ..RuleOptions::default() });
+        assert!(rules.iter().any(|rule| rule.antecedent == integers(&[1]) && rule.consequent == DataTypes::Integer(2) && rule.confidence == 0.5));
+        assert!(rules.iter().all(|rule| rule.antecedent.len() == 1 && rule.support >= 2));
+        assert!(cpt.sequential_rules(&RuleOptions { min_lift: 2.0, ..RuleOptions::default() }).is_empty());
+
+        let json: serde_json::Value = serde_json::from_str(&rules_to_json(&rules).unwrap()).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), rules.len());
+        assert_eq!(json[0]["consequent"], serde_json::json!({"Integer": 3}));
+    }
+}
@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod tests {
+
+    use cpt_rust::cpt::cpt::CPT;
+    use cpt_rust::data_types::data_types::{DataTypes, SequenceAttributes};
+    use cpt_rust::pattern::pattern::{Pattern, PatternItem};
+
+    fn trained_cpt() -> CPT<DataTypes> {
+        let mut cpt = CPT::new();
+        for (sequence, class) in [(vec![1, 2, 4, 9], "a"), (vec![1, 5, 7], "b"), (vec![3, 1, 6, 2], "c"), (vec![1, 2], "d")].iter() {
+            cpt.add_sequence_to_root(
+                sequence.iter().map(|&value| DataTypes::Integer(value)).collect(),
+                Some(vec![SequenceAttributes::ClassStr(class.to_string())])
+            );
+        }
+        cpt
+    }
+
+    #[test]
+    fn parse_patterns() {
+        let pattern = Pattern::parse("^1 * [4..6] ? 'a'$").unwrap();
+        assert!(pattern.anchored_start && pattern.anchored_end);
+        assert_eq!(pattern.items, vec![
+            PatternItem::Literal(DataTypes::Integer(1)),
+            PatternItem::AnyRun,
+            PatternItem::Range(DataTypes::Integer(4), DataTypes::Integer(6)),
+            PatternItem::Any,
+            PatternItem::Literal(DataTypes::U8(b'a')),
+        ]);
+        assert_eq!("? *".parse::<Pattern>().unwrap().items, vec![PatternItem::Any, PatternItem::AnyRun]);
+        assert!(Pattern::parse("[6..4]").is_err());
+        assert!(Pattern::parse("1 x").is_err());
+        assert!(Pattern::parse("^ $").is_err());
+    }
+
+    #[test]
+    fn query_training_sequences() {
+        let cpt = trained_cpt();
+        let query = |pattern: &str| -> Vec<(usize, Vec<usize>)> {
+            cpt.query(&Pattern::parse(pattern).unwrap()).into_iter()
+                .map(|pattern_match| (pattern_match.last_node_id, pattern_match.positions))
+                .collect()
+        };
+        assert_eq!(query("1 * [4..6] ?"), vec![(4, vec![0, 2, 3]), (6, vec![0, 1, 2]), (10, vec![1, 2, 3])]);
+        assert_eq!(query("^ 1 * [4..6] ?"), vec![(4, vec![0, 2, 3]), (6, vec![0, 1, 2])]);
+        // An inverted range cannot be parsed, but can be built, and matches nothing
+        let inverted = Pattern { items: vec![PatternItem::Range(DataTypes::Integer(6), DataTypes::Integer(4))], anchored_start: false, anchored_end: false };
+        assert!(cpt.query(&inverted).is_empty());
+        // The shorter sequence ends inside the longer one
+        assert_eq!(query("1 2 $"), vec![(2, vec![0, 1])]);
+        assert_eq!(query("? ?").len(), 4);
+        assert!(query("8 *").is_empty());
+
+        let matches = cpt.query(&Pattern::parse("3 * 2$").unwrap());
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].attributes, vec![SequenceAttributes::ClassStr("c".to_string())]);
+        assert_eq!(matches[0].sequence, cpt.get_sequence(10));
+    }
+
+    #[test]
+    fn runs_do_not_backtrack_exponentially() {
+        // Without a match, every way of splitting the sequence between the runs would be tried
+        let sequence = vec![DataTypes::Integer(1); 300];
+        assert_eq!(Pattern::parse("* 1 * 1 * 1 * 1 * 1 * 1 * 2").unwrap().find(&sequence), None);
+        // The leftmost match with the shortest runs is still returned
+        assert_eq!(Pattern::parse("* 1 * 1 $").unwrap().find(&sequence[..4]), Some(vec![0, 3]));
+        assert_eq!(Pattern::parse("1 * 1").unwrap().find(&sequence), Some(vec![0, 1]));
+    }
+
+    #[test]
+    fn find_and_count_subsequences() {
+        let mut cpt = trained_cpt();
+        cpt.add_sequence_to_root(vec![DataTypes::Integer(1), DataTypes::Integer(2), DataTypes::Integer(1), DataTypes::Integer(2)], None);
+        let subsequence = [DataTypes::Integer(1), DataTypes::Integer(2)];
+        let occurrences: Vec<(usize, Vec<usize>)> = cpt.find_subsequence(&subsequence).into_iter()
+            .map(|occurrences| (occurrences.last_node_id, occurrences.positions))
+            .collect();
+        assert_eq!(occurrences, vec![(2, vec![0]), (4, vec![0]), (12, vec![0, 2])]);
+        // The last sequence holds the subsequence twice, it counts once
+        assert_eq!(cpt.count_sequences_containing(&subsequence), 3);
+        assert_eq!(cpt.find_subsequence(&subsequence)[0].attributes, vec![SequenceAttributes::ClassStr("d".to_string())]);
+        // So does a sequence added twice
+        cpt.add_sequence_to_root(vec![DataTypes::Integer(1), DataTypes::Integer(2)], Some(vec![]));
+        assert_eq!(cpt.count_sequences_containing(&subsequence), 3);
+
+        assert_eq!(cpt.count_sequences_containing(&[DataTypes::Integer(5), DataTypes::Integer(7)]), 1);
+        assert_eq!(cpt.count_sequences_containing(&[DataTypes::Integer(2), DataTypes::Integer(9), DataTypes::Integer(1)]), 0);
+        assert!(cpt.find_subsequence(&[]).is_empty());
+    }
+}
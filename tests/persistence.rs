@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod tests {
+
+    use serde_json::Value;
+    use cpt_rust::cpt::cpt::CPT;
+    use cpt_rust::data_types::data_types::DataTypes;
+    use cpt_rust::errors::errors::CptError;
+    use cpt_rust::persistence::persistence::ModelFormat;
+
+    fn trained_cpt() -> CPT<DataTypes> {
+        let seq1: [DataTypes; 3] = [DataTypes::Integer(1), DataTypes::Integer(2), DataTypes::Integer(3)];
+        let seq2: [DataTypes; 3] = [DataTypes::Integer(1), DataTypes::Integer(5), DataTypes::Integer(6)];
+        let mut cpt = CPT::new();
+        cpt.add_sequence_to_root(seq1.to_vec(), None);
+        cpt.add_sequence_to_root(seq2.to_vec(), None);
+        cpt
+    }
+
+    #[test]
+    fn models_are_loaded_in_every_format() {
+        let cpt = trained_cpt();
+        let expected = serde_json::from_str::<Value>(&cpt.to_json()).unwrap();
+
+        let loaded = CPT::from_json(&cpt.to_json()).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&loaded.to_json()).unwrap(), expected);
+
+        for format in [ModelFormat::Json, ModelFormat::Binary].iter() {
+            let mut bytes = Vec::<u8>::new();
+            cpt.save(&mut bytes, *format).unwrap();
+            let loaded = CPT::load(bytes.as_slice()).unwrap();
+            assert_eq!(serde_json::from_str::<Value>(&loaded.to_json()).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn inverted_index_is_rebuilt() {
+        let cpt = trained_cpt();
+        let mut json = serde_json::from_str::<Value>(&cpt.to_json()).unwrap();
+        json.as_object_mut().unwrap().remove("inverted_index");
+
+        let loaded = CPT::from_json(&json.to_string()).unwrap();
+        assert!(loaded.inverted_index_is_consistent());
+        assert_eq!(loaded.inverted_index.get_value_ids(DataTypes::Integer(1)), Some(&vec![1]));
+        assert_eq!(loaded.predict(&[DataTypes::Integer(1)], 1).len(), 4);
+    }
+
+    #[test]
+    fn corrupt_models_are_rejected() {
+        let cpt = trained_cpt();
+        let mut json = serde_json::from_str::<Value>(&cpt.to_json()).unwrap();
+        json["nodes"][2]["parent"] = Value::from(3);
+        match CPT::from_json(&json.to_string()) {
+            Err(CptError::CorruptModel(_)) => (),
+            other => panic!("Unexpected result {:?}", other.map(|_| ()))
+        }
+
+        // Nodes 2 and 3 point to each other, and cannot be reached from the root
+        let mut json = serde_json::from_str::<Value>(&cpt.to_json()).unwrap();
+        json["nodes"][1]["children"] = Value::from(vec![4]);
+        json["nodes"][2]["parent"] = Value::from(3);
+        json["nodes"][3]["children"] = Value::from(vec![2]);
+        match CPT::from_json(&json.to_string()) {
+            Err(CptError::CorruptModel(message)) => assert!(message.contains("cannot be reached"), "{}", message),
+            other => panic!("Unexpected result {:?}", other.map(|_| ()))
+        }
+
+        let mut json = serde_json::from_str::<Value>(&cpt.to_json()).unwrap();
+        json["nodes"][0]["children"] = Value::from(vec![1, 1]);
+        match CPT::from_json(&json.to_string()) {
+            Err(CptError::CorruptModel(message)) => assert!(message.contains("reached twice"), "{}", message),
+            other => panic!("Unexpected result {:?}", other.map(|_| ()))
+        }
+
+        match CPT::load(&b"not a model"[..]) {
+            Err(CptError::Json(_)) => (),
+            other => panic!("Unexpected result {:?}", other.map(|_| ()))
+        }
+    }
+}
@@ -0,0 +1,97 @@
+#[cfg(test)]
+mod tests {
+
+    use cpt_rust::cpt::cpt::CPT;
+    use cpt_rust::data_types::data_types::DataTypes;
+    use cpt_rust::errors::errors::CptError;
+    use cpt_rust::evaluation::evaluation;
+    use cpt_rust::predictors::predictors::{predictor_from_name, SequencePredictor, MarkovPredictor, PPMPredictor, PREDICTOR_NAMES};
+    use cpt_rust::predictors::predictors::{HybridPredictor, PredictionSource};
+
+    fn to_sequence(items: &[usize]) -> Vec<DataTypes> {
+        items.iter().map(|&item| DataTypes::Integer(item)).collect()
+    }
+
+    #[test]
+    fn baselines_predict_next_item() {
+        let mut markov = MarkovPredictor::new();
+        markov.train(&to_sequence(&[1, 2, 3]));
+        markov.train(&to_sequence(&[1, 2, 4]));
+        markov.train(&to_sequence(&[5, 2, 3]));
+
+        let predictions = markov.predict_next(&to_sequence(&[7, 2]), 1);
+        assert_eq!(predictions.len(), 2);
+        assert_eq!(predictions[0].0, DataTypes::Integer(3));
+        assert!((predictions[0].1 - 2.0 / 3.0).abs() < 1e-6);
+        assert!(markov.predict_next(&to_sequence(&[7]), 1).is_empty());
+
+        // PPM escapes down to the item frequencies when the context is unknown
+        let mut ppm = PPMPredictor::new(2);
+        ppm.train(&to_sequence(&[1, 2, 3]));
+        assert_eq!(ppm.predict_next(&to_sequence(&[7]), 2).len(), 3);
+    }
+
+    #[test]
+    fn every_predictor_is_evaluated() {
+        let data = "# training\n1 2 3 4\n1 2 3 4\n1,2,3,5\n5 6 -1 7 -2\n1 2 3 4\n";
+        let sequences = evaluation::read_sequences(data.as_bytes()).unwrap();
+        assert_eq!(sequences[3], to_sequence(&[5, 6, 7]));
+
+        let (training, testing) = evaluation::split_dataset(&sequences, 0.2);
+        assert_eq!((training.len(), testing.len()), (4, 1));
+
+        PREDICTOR_NAMES.iter().for_each(|name| {
+            let mut predictor = predictor_from_name(name).unwrap();
+            evaluation::train(predictor.as_mut(), training);
+            let result = evaluation::evaluate(predictor.as_ref(), testing, 2, 1).unwrap();
+            assert_eq!(result.predictor, *name);
+            assert_eq!(result.queries, 1);
+            // The most popular item is 1, which is not the expected one
+            assert_eq!(result.success, if *name == "popularity" { 0 } else { 1 }, "{} did not predict the last item", name);
+        });
+    }
+
+    #[test]
+    fn hybrid_falls_back_on_unseen_prefix() {
+        let mut hybrid = HybridPredictor::new(predictor_from_name("popularity").unwrap());
+        hybrid.train(&to_sequence(&[1, 2, 3]));
+        hybrid.train(&to_sequence(&[1, 2, 3]));
+        hybrid.train(&to_sequence(&[4, 2, 5]));
+
+        // Nothing in the CPT matches the prefix: every item comes from the fallback model
+        let predictions = hybrid.predict_hybrid(&to_sequence(&[8, 9]), 2);
+        assert_eq!(predictions.len(), 5);
+        assert_eq!(predictions[0].item, DataTypes::Integer(2));
+        assert!(predictions.iter().all(|prediction| prediction.source == PredictionSource::Fallback));
+        assert_eq!(predictions[0].score, 1.0);
+
+        // The CPT support is high enough: its scores are scaled like the fallback ones, not raw supports
+        hybrid.train(&to_sequence(&[6, 2, 3]));
+        let predictions = hybrid.predict_hybrid(&to_sequence(&[7, 2]), 1);
+        let predictions: Vec<(DataTypes, f32, PredictionSource)> = predictions.iter().map(|prediction| (prediction.item, prediction.score, prediction.source)).collect();
+        assert_eq!(predictions, vec![(DataTypes::Integer(3), 1.0, PredictionSource::Primary), (DataTypes::Integer(5), 0.5, PredictionSource::Primary)]);
+
+        // The CPT support is too low, so its items are blended with the popular ones
+        let predictions = hybrid.predict_hybrid(&to_sequence(&[4]), 1);
+        assert!(predictions.iter().any(|prediction| prediction.source == PredictionSource::Blended));
+        assert!(predictions.iter().any(|prediction| prediction.source == PredictionSource::Fallback));
+        assert!(predictions.iter().all(|prediction| (0.0..=1.0).contains(&prediction.score)));
+
+        let validation = vec![to_sequence(&[1, 2, 3])];
+        let weight = hybrid.fit_weights(&validation, 2, 1).unwrap();
+        assert!((0.0..=1.0).contains(&weight));
+    }
+
+    #[test]
+    fn cpt_prediction_errors_are_returned() {
+        let mut cpt = CPT::new();
+        cpt.train(&to_sequence(&[1, 2, 3]));
+        // A child pointing to a missing node is an error, not an empty prediction
+        cpt.nodes[3].children.push(42);
+        assert!(matches!(cpt.try_predict_next(&to_sequence(&[1]), 1), Err(CptError::UnknownNode(_))));
+
+        let mut hybrid = HybridPredictor::new(predictor_from_name("popularity").unwrap());
+        hybrid.train(&to_sequence(&[1, 2, 3]));
+        assert!(hybrid.try_predict_next(&to_sequence(&[1]), 1).is_ok());
+    }
+}
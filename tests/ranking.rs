@@ -0,0 +1,59 @@
+#[cfg(test)]
+mod tests {
+
+    use cpt_rust::cpt::cpt::{CPT, MatchOptions, SequenceMatchFunction};
+    use cpt_rust::data_types::data_types::DataTypes;
+    use cpt_rust::ranking::ranking::RankingPolicy;
+
+    fn trained_cpt() -> CPT<DataTypes> {
+        let mut cpt = CPT::new();
+        for sequence in [[1, 2], [1, 9], [3, 2], [8, 8]].iter() {
+            cpt.add_sequence_to_root(sequence.iter().map(|&value| DataTypes::Integer(value)).collect(), None);
+        }
+        cpt
+    }
+
+    #[test]
+    fn ranking_policies() {
+        let cpt = trained_cpt();
+        let query = [DataTypes::Integer(1), DataTypes::Integer(2)];
+        let functions = [SequenceMatchFunction::LongestCommonSubsequence, SequenceMatchFunction::AlgebraicDistance];
+        let ranked = |ranking: RankingPolicy| -> Vec<Vec<usize>> {
+            let options = MatchOptions { ranking, ..MatchOptions::default() };
+            cpt.match_sequence_with_options(&query, false, &functions, &options).unwrap().into_iter()
+                .map(|(nodes, _)| nodes.iter().map(|node| node.0).collect())
+                .collect()
+        };
+        // The longest common subsequences are 0, 1, 1 and 2 items, the middle two tie
+        assert_eq!(ranked(RankingPolicy::Lexicographic), vec![vec![6, 7], vec![1, 3], vec![4, 5], vec![1, 2]]);
+        assert_eq!(cpt.match_sequence(&query, false, &functions).len(), 4);
+        // Ties are broken on the node ids, the lower ids last
+        assert_eq!(ranked(RankingPolicy::WeightedSum(vec![1.0, 0.0])), vec![vec![6, 7], vec![4, 5], vec![1, 3], vec![1, 2]]);
+        assert_eq!(ranked(RankingPolicy::WeightedSum(vec![0.0, 1.0])), vec![vec![6, 7], vec![1, 3], vec![4, 5], vec![1, 2]]);
+        assert_eq!(ranked(RankingPolicy::ParetoFront), vec![vec![1, 2]]);
+
+        // Every match has the length of the query, so the lengths tie and the distance ranks them
+        let length_first = [SequenceMatchFunction::SequenceLength, SequenceMatchFunction::AlgebraicDistance];
+        let ranked_nodes = |functions: &[SequenceMatchFunction]| -> Vec<Vec<usize>> {
+            cpt.match_sequence(&query, false, functions).into_iter()
+                .map(|(nodes, _)| nodes.iter().map(|node| node.0).collect())
+                .collect()
+        };
+        assert_eq!(ranked_nodes(&length_first), vec![vec![6, 7], vec![1, 3], vec![4, 5], vec![1, 2]]);
+        // With the length alone, only the node ids break the tie
+        assert_eq!(ranked_nodes(&length_first[..1]), vec![vec![6, 7], vec![4, 5], vec![1, 3], vec![1, 2]]);
+        for _ in 0..5 {
+            assert_eq!(trained_cpt().match_sequence(&query, false, &[SequenceMatchFunction::SequenceLength]).last().unwrap().0.len(), 2);
+            assert_eq!(ranked(RankingPolicy::WeightedSum(vec![1.0, 0.0]))[1], vec![4, 5]);
+        }
+
+        let options = MatchOptions { ranking: RankingPolicy::WeightedSum(vec![1.0]), ..MatchOptions::default() };
+        assert!(cpt.match_sequence_with_options(&query, false, &functions, &options).is_err());
+
+        // The user defined metrics are ranked the same way
+        let options = MatchOptions { ranking: RankingPolicy::ParetoFront, max_results: Some(1), ..MatchOptions::default() };
+        let matches = cpt.match_sequence_with_metrics(&query, false, &[&SequenceMatchFunction::SequenceLength, &functions[1]], &options).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].1, vec![2.0, 0.0]);
+    }
+}
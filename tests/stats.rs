@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod tests {
+
+    use std::collections::BTreeMap;
+    use cpt_rust::cpt::cpt::CPT;
+    use cpt_rust::data_types::data_types::DataTypes;
+
+    fn histogram(entries: &[(usize, usize)]) -> BTreeMap<usize, usize> {
+        entries.iter().copied().collect()
+    }
+
+    #[test]
+    fn tree_statistics() {
+        let mut cpt = CPT::new();
+        for sequence in [vec![1, 2, 3], vec![1, 2, 4], vec![1, 2], vec![5]].iter() {
+            cpt.add_sequence_to_root(sequence.iter().map(|&value| DataTypes::Integer(value)).collect(), None);
+        }
+        let stats = cpt.stats();
+        assert_eq!(stats.node_count, 6);
+        assert_eq!(stats.sequence_count, 4);
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(stats.depth_histogram, histogram(&[(1, 2), (2, 1), (3, 2)]));
+        assert_eq!(stats.length_histogram, histogram(&[(1, 1), (2, 1), (3, 2)]));
+        assert_eq!(stats.branching_histogram, histogram(&[(0, 3), (1, 1), (2, 2)]));
+        assert!((stats.mean_branching - 5.0 / 3.0).abs() < 1e-6);
+        assert_eq!(stats.vocabulary_size, 5);
+        assert_eq!(&stats.item_frequencies[..2], &[(DataTypes::Integer(1), 3), (DataTypes::Integer(2), 3)]);
+        assert_eq!(stats.items_inserted, Some(9));
+        assert!((stats.compression_ratio.unwrap() - 1.8).abs() < 1e-6);
+        assert_eq!(stats.index_node_ids, 5);
+        assert_eq!(stats.memory, cpt.memory_usage());
+
+        let json: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+        assert_eq!(json["sequence_count"], 4);
+        assert_eq!(json["length_histogram"]["3"], 2);
+
+        let empty = CPT::new().stats();
+        assert_eq!((empty.node_count, empty.sequence_count, empty.max_depth), (1, 0, 0));
+        assert_eq!(empty.compression_ratio, Some(0.0));
+    }
+
+    #[test]
+    fn identical_sequences_count_as_inserted() {
+        let sequence = vec![DataTypes::Integer(1), DataTypes::Integer(2), DataTypes::Integer(3)];
+        let mut cpt = CPT::new();
+        cpt.add_sequence_to_root(sequence.clone(), None);
+        cpt.add_sequence_to_root(sequence.clone(), None);
+        let stats = cpt.stats();
+        // The sequence is stored once, but both insertions are counted
+        assert_eq!((stats.sequence_count, stats.items_inserted), (1, Some(6)));
+        assert!((stats.compression_ratio.unwrap() - 2.0).abs() < 1e-6);
+
+        let mut other = CPT::new();
+        other.add_sequence_to_root(sequence, None);
+        other.add_sequence_to_root(vec![DataTypes::Integer(4)], None);
+        cpt.merge(&other).unwrap();
+        assert_eq!(cpt.stats().items_inserted, Some(10));
+        assert_eq!(cpt.compact().to_cpt().items_inserted, Some(10));
+
+        // The count is not saved, it is unknown once the model is loaded
+        let loaded = CPT::from_json(&cpt.to_json()).unwrap();
+        assert_eq!((loaded.stats().items_inserted, loaded.stats().compression_ratio), (None, None));
+        assert_eq!(CPT::read_binary(cpt.to_binary().as_slice()).unwrap().items_inserted, None);
+    }
+}